@@ -0,0 +1,294 @@
+use bevy::prelude::*;
+use crate::constants::*;
+use crate::resources::*;
+
+
+// Debug overlay text, only spawned while the overlay is shown: the full overlay (F3), or just the
+// input latency readout (F4)
+#[derive(Component)]
+pub struct DebugText {
+    pub latency_only: bool,
+}
+
+
+// Camera viewing the arena (as opposed to the UI camera)
+#[derive(Component)]
+pub struct MainCamera;
+
+
+// Main camera's zoom: the scale fitting the arena in the window, briefly punched in on paddle hits
+#[derive(Component)]
+pub struct CameraZoom {
+    pub fit: f32,
+    pub punch: Timer,
+}
+
+
+// Marker component for player
+#[derive(Component)]
+pub struct Player;
+
+
+// Marker component for opponent
+#[derive(Component)]
+pub struct Opponent;
+
+
+// Marker component for the net down the middle
+#[derive(Component)]
+pub struct Net;
+
+
+// Static part of the field laid out from the arena size (net, zone marks, magnet, solid net),
+// rebuilt whenever the arena is resized
+#[derive(Component)]
+pub struct ArenaFixture;
+
+
+// Marker component for text drawn in the theme's text color
+#[derive(Component)]
+pub struct ThemedText;
+
+
+// Marker component for ball
+#[derive(Component)]
+pub struct Ball;
+
+
+// Ball caught by the sticky player paddle, held at an offset from the paddle center
+#[derive(Component)]
+pub struct Caught {
+    pub offset_y: f32,
+    pub hold_timer: Timer,
+}
+
+
+// Tracks how long the ball has been travelling (nearly) horizontally, or not moving at all
+#[derive(Component, Default)]
+pub struct StuckDetector {
+    pub flat_time: f32,
+    pub still_time: f32,
+    // Position the ball has stayed close to since it last moved
+    pub anchor: Vec2,
+}
+
+
+// Pickup that enlarges the paddle that last touched the ball passing over it
+#[derive(Component)]
+pub struct PowerUp;
+
+
+// Target to knock out with the ball, in target practice
+#[derive(Component)]
+pub struct Target;
+
+
+// Paddle enlarged by a power-up, until the timer runs out
+#[derive(Component)]
+pub struct Enlarged(pub Timer);
+
+
+// 3-2-1 countdown text shown until the next serve
+#[derive(Component)]
+pub struct Countdown;
+
+
+// "Let" announcement, shown until the timer runs out
+#[derive(Component)]
+pub struct LetIndicator(pub Timer);
+
+
+// "Comeback!" banner, shown for a moment after a side scores from far behind
+#[derive(Component)]
+pub struct ComebackBanner(pub Timer);
+
+
+// One edge of the border pulsing around the arena on match point, pointing out from its center
+#[derive(Component)]
+pub struct MatchPointBorder(pub Vec2);
+
+
+// "MATCH POINT" banner, shown while the next goal could win the match
+#[derive(Component)]
+pub struct MatchPointBanner;
+
+
+// Multi-ball color of the ball, drawn in place of the theme's ball color (and by its trail)
+#[derive(Component)]
+pub struct BallColor(pub Color);
+
+
+// Ghost left behind the ball, shrinking and fading out until the timer runs out
+#[derive(Component)]
+pub struct TrailFade(pub Timer);
+
+
+// Spark thrown off a paddle hit, drifting and fading out until the timer runs out
+#[derive(Component)]
+pub struct Particle {
+    pub timer: Timer,
+    pub velocity: Vec2,
+}
+
+
+// Paddle that last touched the ball
+#[derive(Component)]
+pub struct LastTouched(pub Entity);
+
+
+// Paddle stamina, drained by movement and recovered while still
+#[derive(Component)]
+pub struct Stamina {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Stamina {
+    /// Drain stamina for the given movement distance, or recover it when (nearly) still
+    pub fn update(&mut self, distance: f32) {
+        if distance.abs() < 0.5 {
+            self.current += STAMINA_RECOVERY * TIME_STEP;
+        } else {
+            self.current -= distance.abs() * STAMINA_DRAIN;
+        }
+        self.current = self.current.clamp(0., self.max);
+    }
+
+    /// Movement speed multiplier, slowing the paddle when stamina is low
+    pub fn speed_factor(&self) -> f32 {
+        if self.current < self.max * STAMINA_LOW_FRACTION {
+            STAMINA_SLOW_FACTOR
+        } else {
+            1.
+        }
+    }
+}
+
+
+// Bar displaying the stamina of the given paddle
+#[derive(Component)]
+pub struct StaminaBar(pub Entity);
+
+
+// Stationary magnet that pulls (positive strength) or pushes (negative) balls within its radius
+#[derive(Component)]
+pub struct Magnet {
+    pub strength: f32,
+    pub radius: f32,
+}
+
+
+// Overlay cell of the heatmap, holding its index into the heatmap counts
+#[derive(Component)]
+pub struct HeatmapCell(pub usize);
+
+
+// Whether the player served the ball, and how much longer it can't score against whoever served it
+#[derive(Component)]
+pub struct Serve {
+    pub immunity: Timer,
+    pub by_player: bool,
+}
+
+
+// Track velocity of an entity
+#[derive(Component)]
+pub struct Velocity(pub Vec2);
+
+
+// Collider component, deciding how the ball responds when hitting it
+// (collisions based on sprite custom_size)
+#[derive(Component)]
+pub enum ColliderKind {
+    // Bounces the ball back at an angle based on where it hit
+    Paddle,
+    // Deflects the ball straight back off whichever side it hit
+    Obstacle,
+    // Knocked out by the ball, which carries straight on
+    Target,
+    // Collected by the ball, enlarging the paddle that last touched it
+    PowerUp,
+}
+
+
+// UI text resized with the UI scale setting, holding its font size at a scale of 1
+#[derive(Component)]
+pub struct ScaledText(pub f32);
+
+
+// Marker component for scoreboard text, on both scores
+#[derive(Component)]
+pub struct ScoreText;
+
+
+// Marker component for the player's score, left of the net
+#[derive(Component)]
+pub struct PlayerScoreText;
+
+
+// Marker component for the opponent's score, right of the net
+#[derive(Component)]
+pub struct OpponentScoreText;
+
+
+// Marker component for the timed match clock text
+#[derive(Component)]
+pub struct MatchClockText;
+
+
+// Marker component for the rally counter text under the scoreboard
+#[derive(Component)]
+pub struct RallyText;
+
+
+// Marker component for the series score text under the scoreboard
+#[derive(Component)]
+pub struct SeriesText;
+
+
+// Marker component for entities that only exist while a game is being played
+#[derive(Component)]
+pub struct Gameplay;
+
+
+// Marker component for the title screen UI
+#[derive(Component)]
+pub struct MenuUi;
+
+
+// Marker component for the settings screen UI
+#[derive(Component)]
+pub struct SettingsUi;
+
+
+// Marker component for the key bindings screen UI
+#[derive(Component)]
+pub struct KeyBindingsUi;
+
+
+// Button on a menu screen, numbered top to bottom for keyboard navigation
+#[derive(Component)]
+pub struct MenuButton {
+    pub action: MenuAction,
+    pub index: usize,
+}
+
+
+// Marker component for the pause overlay
+#[derive(Component)]
+pub struct PauseUi;
+
+
+// Stand-in for a paddle or ball, drawn where it was on the replay frame being shown
+#[derive(Component)]
+pub struct ReplayGhost;
+
+
+// Marker component for the game over UI
+#[derive(Component)]
+pub struct GameOverText;
+
+
+// Pop/flash animation timer for a score on the scoreboard
+#[derive(Component)]
+pub struct ScoreAnimation(pub Timer);
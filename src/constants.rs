@@ -0,0 +1,236 @@
+use bevy::math::const_vec2;
+use bevy::prelude::*;
+
+
+// Physics framerate
+pub const TIME_STEP: f32 = 1.0 / 60.0;
+// Time scale during a rally on match point, and how much it can change per second easing in and out
+pub const MATCH_POINT_TIME_SCALE: f32 = 0.7;
+pub const TIME_SCALE_EASE_RATE: f32 = 2.0;
+// Endless mode: goals the player can concede before it's over, seconds until the AI and the serve
+// reach their hardest, and how much sharper the AI and faster the serve get by then
+pub const ENDLESS_LIVES: u16 = 3;
+pub const ENDLESS_RAMP_TIME: f32 = 180.0;
+pub const ENDLESS_MAX_AI_BOOST: f32 = 2.0;
+pub const ENDLESS_MAX_SERVE_BOOST: f32 = 1.5;
+// Match point border around the arena, pulsing this many times a second, and "MATCH POINT" banner
+pub const MATCH_POINT_BORDER_THICKNESS: f32 = 6.0;
+pub const MATCH_POINT_BORDER_COLOR: Color = Color::rgb(0.95, 0.75, 0.1);
+pub const MATCH_POINT_PULSE_RATE: f32 = 1.5;
+pub const MATCH_POINT_FONT_SIZE: f32 = 40.0;
+// Seconds of play held for replaying on demand, and the range the setting can be set within
+pub const REPLAY_SECONDS: f32 = 5.0;
+pub const MIN_REPLAY_SECONDS: f32 = 3.0;
+pub const MAX_REPLAY_SECONDS: f32 = 10.0;
+pub const REPLAY_GHOST_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.7);
+pub const PAUSE_OVERLAY_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+
+// Window/arena size unless overridden with --width/--height, and the smallest size allowed
+pub const DEFAULT_ARENA_WIDTH: f32 = 800.0;
+pub const DEFAULT_ARENA_HEIGHT: f32 = 600.0;
+pub const MIN_ARENA_SIZE: f32 = 200.0;
+
+// Paddle and ball sizes, by default (the game reads them, and the other tuning defaults below, from
+// GameConfig)
+pub const PADDLE_SIZE: Vec2 = const_vec2!([6., 46.]);
+pub const BALL_SIZE: Vec2 = const_vec2!([8., 8.]);
+// Dashes making up the net, drawn behind the ball and paddles (but in front of the heatmap)
+pub const NET_WIDTH: f32 = 3.;
+pub const NET_DASH_LENGTH: f32 = 14.;
+pub const NET_DASH_GAP: f32 = 10.;
+pub const NET_Z: f32 = -0.5;
+// Goal areas along each side edge, centered this far in from it
+pub const GUTTER_WIDTH: f32 = 26.;
+pub const GUTTER_INSET: f32 = 3.;
+
+pub const SCORE_FONT_SIZE: f32 = 60.0;
+// Gap between the net and each side's score
+pub const SCORE_NET_GAP: f32 = 60.0;
+// Length of a timed match, in seconds
+pub const MATCH_DURATION: f32 = 120.0;
+// Seconds counted down before each serve
+pub const SERVE_COUNTDOWN: f32 = 3.0;
+pub const COUNTDOWN_FONT_SIZE: f32 = 80.0;
+pub const SERVE_PROMPT_FONT_SIZE: f32 = 32.0;
+// "Let" shown above the serve countdown after a rally is called off, and for how long
+pub const LET_FONT_SIZE: f32 = 48.0;
+pub const LET_DURATION: f32 = 1.5;
+// Deficit a side must be scoring from for the goal to be cheered as a comeback
+pub const COMEBACK_GAP: u16 = 3;
+pub const COMEBACK_FONT_SIZE: f32 = 48.0;
+pub const COMEBACK_DURATION: f32 = 1.5;
+pub const COMEBACK_COLOR: Color = Color::rgb(0.3, 0.9, 0.5);
+// Font sizes of the rest of the UI text, before the UI scale setting is applied
+pub const TITLE_FONT_SIZE: f32 = 80.0;
+pub const HEADING_FONT_SIZE: f32 = 60.0;
+pub const FINAL_SCORE_FONT_SIZE: f32 = 40.0;
+pub const MATCH_CLOCK_FONT_SIZE: f32 = 28.0;
+pub const STATS_FONT_SIZE: f32 = 24.0;
+pub const HUD_FONT_SIZE: f32 = 20.0;
+pub const DEBUG_FONT_SIZE: f32 = 16.0;
+// UI scales offered on the settings screen, smallest first
+pub const UI_SCALE_PRESETS: [f32; 4] = [0.75, 1.0, 1.25, 1.5];
+pub const SCORE_FLASH_COLOR: Color = Color::rgb(1.0, 0.85, 0.3);
+pub const SCORE_POP_SCALE: f32 = 0.5;
+pub const SCORE_POP_DURATION: f32 = 0.3;
+// How far the camera zooms in on a paddle hit (as a fraction of its scale), and how long it takes
+// to zoom back out (a zoom of 0 turns it off)
+pub const CAMERA_PUNCH_ZOOM: f32 = 0.03;
+pub const CAMERA_PUNCH_DURATION: f32 = 0.15;
+// Menu buttons, highlighted while focused (hovered, or selected with the keyboard) and pressed
+pub const MENU_BUTTON_SIZE: Vec2 = const_vec2!([260., 56.]);
+pub const MENU_BUTTON_MARGIN: f32 = 6.0;
+pub const MENU_BUTTON_FONT_SIZE: f32 = 28.0;
+pub const MENU_BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+pub const MENU_BUTTON_FOCUSED_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
+pub const MENU_BUTTON_PRESSED_COLOR: Color = Color::rgb(0.45, 0.45, 0.45);
+// Seconds the title menu sits without input before the attract mode demo starts behind it
+pub const DEMO_IDLE_TIME: f32 = 20.0;
+// Paddle colors when tinted to tell the sides apart, told apart with any form of colorblindness
+pub const PLAYER_PADDLE_TINT: Color = Color::rgb(0.34, 0.71, 0.91);
+pub const OPPONENT_PADDLE_TINT: Color = Color::rgb(0.9, 0.6, 0.0);
+
+pub const MUSIC_VOLUME: f32 = 0.1;
+// Tense layer over the music on match point
+pub const MATCH_POINT_MUSIC_VOLUME: f32 = 0.3;
+// Extra music layers, each faded in over its share of the way to match point, so the music builds as
+// either side closes in on winning
+pub const MUSIC_STEMS: [&str; 2] = ["sounds/MusicStem1.wav", "sounds/MusicStem2.wav"];
+pub const MUSIC_STEM_VOLUME: f32 = 0.1;
+pub const HIT_VOLUME: f32 = 1.0;
+pub const WALL_VOLUME: f32 = 0.6;
+// Playback speed of the wall sound, raising its pitch
+pub const WALL_SOUND_SPEED: f32 = 1.5;
+pub const GOAL_VOLUME: f32 = 0.4;
+pub const COMEBACK_VOLUME: f32 = 0.5;
+// Serve charge-up sound, started this long before the serve so it peaks as the ball launches
+pub const SERVE_CHARGE_VOLUME: f32 = 0.5;
+pub const SERVE_CHARGE_DURATION: f32 = 0.6;
+// Fraction of the music volume removed while ducking under the goal sound
+pub const MUSIC_DUCK_AMOUNT: f32 = 0.7;
+pub const MUSIC_DUCK_DURATION: f32 = 0.5;
+
+// How quickly the idle opponent drifts back to its rest position
+pub const AI_REST_TRACKING: f32 = 3.0;
+
+// Longest time the sticky paddle can hold the ball before it launches anyway
+pub const STICKY_MAX_HOLD: f32 = 1.5;
+
+pub const POWER_UP_SIZE: Vec2 = const_vec2!([16., 16.]);
+pub const POWER_UP_SPAWN_INTERVAL: f32 = 8.0;
+// Paddle height multiplier while enlarged by a power-up, and how long it lasts
+pub const POWER_UP_PADDLE_SCALE: f32 = 1.6;
+pub const POWER_UP_DURATION: f32 = 8.0;
+// Targets set out in a column across the opponent's half in target practice
+pub const TARGET_COUNT: usize = 3;
+pub const TARGET_SIZE: Vec2 = const_vec2!([16., 16.]);
+
+pub const STAMINA_MAX: f32 = 100.;
+// Stamina drained per unit of paddle movement, and recovered per second when still
+pub const STAMINA_DRAIN: f32 = 0.08;
+pub const STAMINA_RECOVERY: f32 = 30.;
+// Below this fraction of max stamina the paddle is slowed down
+pub const STAMINA_LOW_FRACTION: f32 = 0.2;
+pub const STAMINA_SLOW_FACTOR: f32 = 0.4;
+pub const STAMINA_BAR_SIZE: Vec2 = const_vec2!([60., 4.]);
+
+// Largest velocity change a magnet can apply to a ball in a single tick
+pub const MAGNET_MAX_NUDGE: f32 = 20.;
+
+// How long ball trail ghosts take to fade out, and how opaque they start
+pub const TRAIL_FADE_DURATION: f32 = 0.3;
+pub const TRAIL_ALPHA: f32 = 0.4;
+
+// Sparks thrown off each paddle hit, up to a cap on how many can be around at once
+pub const PARTICLES_PER_HIT: usize = 8;
+pub const MAX_PARTICLES: usize = 64;
+pub const PARTICLE_SIZE: Vec2 = const_vec2!([3., 3.]);
+pub const PARTICLE_LIFETIME: f32 = 0.4;
+pub const PARTICLE_MIN_SPEED: f32 = 80.;
+pub const PARTICLE_MAX_SPEED: f32 = 220.;
+// Widest angle (in radians) either side of straight out from the paddle a spark can fly
+pub const PARTICLE_SPREAD: f32 = 1.2;
+
+// Grid dimensions of the ball position heatmap
+pub const HEATMAP_COLUMNS: usize = 40;
+pub const HEATMAP_ROWS: usize = 30;
+// Opacity of the hottest heatmap cell, so the overlay never hides live play
+pub const HEATMAP_MAX_ALPHA: f32 = 0.45;
+
+// Serve speed multiplier for the first onboarding serve, ramping up to full speed
+pub const ONBOARDING_SERVE_SPEED: f32 = 0.6;
+
+// Seconds after a serve during which the ball can't score against the side that served it
+pub const SERVE_GUTTER_IMMUNITY: f32 = 0.5;
+
+// Corner serves start halfway between the net and the serving side's edge, this far in from the top
+// or bottom wall, and head back across the arena at this angle (radians) from straight across
+pub const CORNER_SERVE_INSET: f32 = 40.;
+pub const CORNER_SERVE_ANGLE: f32 = 0.4;
+
+// Top player paddle speed when controlled with the keyboard or a gamepad
+pub const PLAYER_PADDLE_SPEED: f32 = 500.;
+// How quickly paddles can speed up, and the extra deceleration from friction when slowing down or turning
+pub const PADDLE_MAX_ACCEL: f32 = 6000.;
+pub const PADDLE_FRICTION: f32 = 3000.;
+
+// Gamepad stick deflection below which input is ignored, so a resting stick doesn't drift
+pub const GAMEPAD_DEADZONE: f32 = 0.15;
+
+// Fraction of a paddle's Y velocity thrown into the ball on a bounce, and the resulting Y speed limit
+pub const PADDLE_SPIN_FACTOR: f32 = 0.3;
+pub const MAX_BOUNCE_Y_SPEED: f32 = 600.;
+pub const BOUNCE_ANGLE_MULTIPLIER: f32 = 22.0;
+pub const BALL_SPEED: f32 = 500.;
+// Fastest the ball may travel in any direction, however much it's sped up by rallies and spin
+pub const MAX_BALL_SPEED: f32 = 1100.;
+// Ball X speed multiplier applied on each paddle hit, and the most the rally can speed it up overall
+pub const RALLY_SPEED_RAMP: f32 = 1.05;
+pub const RALLY_MAX_SPEED_FACTOR: f32 = 2.0;
+// Fraction of its size the ball loses with each paddle hit of a rally (0 = never shrinks), down to
+// MIN_BALL_SCALE of its full size. It's back to full size on the next serve
+pub const BALL_SHRINK_PER_HIT: f32 = 0.;
+pub const MIN_BALL_SCALE: f32 = 0.5;
+// Physics freeze on each paddle hit (hit-stop), in seconds (0 = off), kept short enough not to
+// feel sluggish
+pub const HIT_STOP_DURATION: f32 = 0.;
+pub const MAX_HIT_STOP: f32 = 0.06;
+// Paddle hits after which a rally is called off as a let, with no point scored (None = no limit)
+pub const MAX_RALLY_LENGTH: Option<u32> = None;
+// In multi-ball mode, another ball joins the rally every MULTI_BALL_HITS_PER_BALL paddle hits, up to MULTI_BALL_MAX at once
+// by default. Once the cap is reached, the hitting ball is sped up by MULTI_BALL_CAPPED_BOOST instead
+pub const MULTI_BALL_HITS_PER_BALL: u32 = 4;
+pub const MULTI_BALL_MAX: usize = 3;
+pub const MULTI_BALL_CAPPED_BOOST: f32 = 1.15;
+// Colors given to the balls in multi-ball, so each can be followed (the first not already taken by
+// a ball in play), told apart with any form of colorblindness
+pub const MULTI_BALL_PALETTE: [Color; 6] = [
+    Color::rgb(0.9, 0.6, 0.0),
+    Color::rgb(0.34, 0.71, 0.91),
+    Color::rgb(0.0, 0.62, 0.45),
+    Color::rgb(0.94, 0.89, 0.26),
+    Color::rgb(0.84, 0.37, 0.0),
+    Color::rgb(0.8, 0.47, 0.65),
+];
+// Ball moving this slowly on Y for STUCK_FLAT_TIME seconds is nudged by a random Y speed of up to STUCK_NUDGE_SPEED
+pub const STUCK_MIN_Y_SPEED: f32 = 20.;
+pub const STUCK_FLAT_TIME: f32 = 4.0;
+pub const STUCK_NUDGE_SPEED: f32 = 150.;
+// Ball staying within STUCK_MIN_MOVEMENT of one spot for STUCK_STILL_TIME seconds is respawned
+pub const STUCK_MIN_MOVEMENT: f32 = 2.;
+pub const STUCK_STILL_TIME: f32 = 2.0;
+
+// Seed for the game's randomness unless overridden with --seed
+pub const DEFAULT_SEED: u64 = 0x90AB;
+
+// Files kept in the user's data directory
+pub const RECORDS_FILE: &str = "records.ron";
+pub const SETTINGS_FILE: &str = "settings.ron";
+pub const REPLAY_FILE: &str = "replay.ron";
+// Longest rallies ever played, how many are kept, and how many the game over screen lists
+pub const RALLY_LEADERBOARD_FILE: &str = "rally_leaderboard.ron";
+pub const RALLY_LEADERBOARD_SIZE: usize = 10;
+pub const RALLY_LEADERBOARD_SHOWN: usize = 3;
+// Every finished match's stats, a row each, for tracking improvement over many sessions
+pub const MATCH_STATS_FILE: &str = "match_stats.csv";
+pub const MATCH_STATS_HEADER: &str = "date,mode,difficulty,player_score,opponent_score,duration_secs,longest_rally";
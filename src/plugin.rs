@@ -0,0 +1,342 @@
+use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
+use bevy::input::mouse::MouseMotion;
+use bevy::prelude::*;
+use crate::components::*;
+use crate::constants::*;
+use crate::resources::*;
+use crate::systems::*;
+
+
+// All of the game itself: the simulation, plus its UI, audio, menus and persistence, played in the
+// given arena with randomness from the given seed
+pub struct PongGamePlugin {
+    pub arena: Arena,
+    pub seed: u64,
+    pub replay_mode: ReplayMode,
+    pub classic_mode: ClassicMode,
+}
+
+impl Plugin for PongGamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(PongSimulationPlugin { arena: self.arena, seed: self.seed })
+            .add_plugin(MenuPlugin)
+            .insert_resource(self.replay_mode)
+            .insert_resource(self.classic_mode)
+            .add_plugin(FrameTimeDiagnosticsPlugin)
+            .insert_resource(ClearColor(Color::BLACK))
+            .insert_resource(UiScale(1.0))
+            .insert_resource(load_data_file::<Records>(RECORDS_FILE))
+            .insert_resource(load_data_file::<RallyLeaderboard>(RALLY_LEADERBOARD_FILE))
+            .insert_resource(Settings::default())
+            .insert_resource(BallTrail(true))
+            .insert_resource(SpeedTintBallColors(false))
+            .insert_resource(ReduceMotion(false))
+            .insert_resource(CameraRecenter(true))
+            .insert_resource(ReplayPlayback(None))
+            .insert_resource(Theme::from_preset(ThemePreset::Classic))
+            .insert_resource(AudioSettings { master_volume: 1.0, muted: false, music_enabled: true })
+            .insert_resource(MusicStems { enabled: true, sinks: Vec::new() })
+            .insert_resource(MusicDuck(finished_timer(MUSIC_DUCK_DURATION)))
+            .insert_resource(ServeCharge { enabled: true, started: false, sink: None })
+            .insert_resource(ExportMatchStats(true))
+            .insert_resource(MatchPointEmphasis(true))
+            .insert_resource(SessionStats::default())
+            .insert_resource(CarrySessionStats(true))
+            // Settings are applied before anything (e.g. the music) depends on them
+            .add_startup_system_to_stage(StartupStage::PreStartup, load_settings)
+            .add_startup_system(setup)
+            .add_startup_system(apply_classic_mode)
+            .add_system(apply_ui_scale)
+            .add_system(toggle_mute)
+            .add_system(toggle_fullscreen)
+            .add_system(resize_arena.before(fit_gameplay_to_arena))
+            .add_system(fit_camera_to_window.after(resize_arena))
+            .add_system(recenter_camera.after(fit_camera_to_window).after(ball_spawner).before(zoom_camera))
+            .add_system(zoom_camera.after(fit_camera_to_window))
+            .add_startup_system(setup_input_latency_diagnostic)
+            .add_system(toggle_debug_overlay)
+            .add_system(record_input_latency.before(debug_overlay))
+            .add_system(debug_overlay.after(toggle_debug_overlay))
+            .add_system(duck_music.after(toggle_mute))
+            .add_system(play_match_point_music.after(detect_match_point).after(toggle_mute))
+            .add_system(play_music_stems.after(toggle_mute))
+            .add_system(show_match_point.after(detect_match_point))
+            // After the game over check, so a match that's just ended can't be paused instead
+            .add_system(toggle_pause.after(check_game_over))
+            .add_system(pause_on_focus_loss.after(check_game_over))
+            .add_system(toggle_input_mode)
+            .add_system(select_difficulty)
+            .add_system(track_gamepads)
+            .add_system(update_stamina_bars)
+            .add_system(toggle_heatmap)
+            .add_system(update_heatmap_cells.after(toggle_heatmap))
+            .add_system(play_sounds)
+            .add_system(start_hit_stop.after(process_collisions))
+            .add_system(cycle_theme)
+            // After this frame's spawns, so new entities are never drawn in the wrong colors
+            .add_system_to_stage(CoreStage::PostUpdate, apply_theme)
+            .add_system_to_stage(CoreStage::PostUpdate, tint_balls_by_speed)
+            .add_system_set(
+                SystemSet::on_enter(AppState::Playing)
+                    .with_system(spawn_scoreboard)
+                    .with_system(spawn_match_point_emphasis)
+                    .with_system(grab_cursor)
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(countdown_display.after(ball_spawner))
+                    .with_system(play_serve_charge.after(ball_spawner))
+                    .with_system(let_indicator)
+                    .with_system(comeback_cue)
+                    .with_system(spawn_ball_trail)
+                    .with_system(fade_ball_trail)
+                    .with_system(spawn_paddle_particles)
+                    .with_system(update_particles)
+                    .with_system(update_scoreboard.before(check_game_over))
+                    .with_system(update_series_text.after(check_game_over))
+                    .with_system(update_rally_text)
+                    .with_system(update_match_clock_text.after(check_game_over))
+                    .with_system(animate_score.after(update_scoreboard).after(apply_ui_scale))
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Playing)
+                    .with_system(release_cursor)
+                    .with_system(save_replay)
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Paused)
+                    .with_system(spawn_pause_overlay)
+                    .with_system(release_cursor)
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Paused)
+                    .with_system(toggle_instant_replay.before(play_instant_replay))
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Paused)
+                    .with_system(despawn_with::<PauseUi>)
+                    .with_system(stop_instant_replay)
+                    .with_system(grab_cursor)
+            )
+            .add_system(play_instant_replay)
+            .add_system(record_longest_rally)
+            .add_system(record_rally_leaderboard)
+            .add_system(record_most_goals.after(check_game_over))
+            .add_system(record_session_stats.after(check_game_over))
+            .add_system(export_match_stats.after(check_game_over))
+            .add_system(save_settings.after(apply_menu_actions))
+            .add_system(reset_rally.with_run_criteria(practice_helpers_enabled))
+            .add_system_set(
+                SystemSet::on_enter(AppState::GameOver)
+                    .with_system(spawn_game_over_text)
+            )
+            .add_system_set(SystemSet::on_update(AppState::GameOver).with_system(apply_game_over_actions.after(navigate_menu)))
+            .add_system_set(SystemSet::on_exit(AppState::GameOver).with_system(despawn_with::<GameOverText>));
+
+        // Plays back the recorded match instead of the one seeded above
+        if self.replay_mode == ReplayMode::Playback {
+            app.insert_resource(load_data_file::<Replay>(REPLAY_FILE));
+        }
+
+        // Debug helper, not available in release builds
+        #[cfg(debug_assertions)]
+        app.add_system_set(SystemSet::on_update(AppState::Paused).with_system(step_once));
+    }
+}
+
+
+// Title, settings and key bindings screens, navigable with the mouse or keyboard, with a demo match
+// playing behind the title screen whenever it's left idle
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MenuFocus(0))
+            .insert_resource(Rebinding(None))
+            .add_event::<MenuAction>()
+            .add_system_set(SystemSet::on_enter(AppState::Menu).with_system(spawn_menu))
+            .add_system_set(SystemSet::on_update(AppState::Menu).with_system(start_demo_when_idle))
+            .add_system_set(SystemSet::on_update(AppState::Demo).with_system(end_demo_on_input))
+            .add_system_set(SystemSet::on_exit(AppState::Menu).with_system(despawn_with::<MenuUi>))
+            .add_system_set(SystemSet::on_enter(AppState::Settings).with_system(spawn_settings_menu))
+            .add_system_set(SystemSet::on_update(AppState::Settings).with_system(update_settings_labels.after(save_settings)))
+            .add_system_set(SystemSet::on_exit(AppState::Settings).with_system(despawn_with::<SettingsUi>))
+            .add_system_set(SystemSet::on_enter(AppState::KeyBindings).with_system(spawn_key_bindings_menu))
+            .add_system_set(
+                SystemSet::on_update(AppState::KeyBindings)
+                    // Before navigation, so the key press that starts rebinding isn't taken as the new key
+                    .with_system(capture_rebind.before(navigate_menu))
+                    .with_system(update_key_binding_labels.after(apply_menu_actions))
+            )
+            .add_system_set(SystemSet::on_exit(AppState::KeyBindings).with_system(despawn_with::<KeyBindingsUi>))
+            .add_system(navigate_menu)
+            .add_system(apply_menu_actions.after(navigate_menu))
+            .add_system(color_menu_buttons.after(navigate_menu));
+    }
+}
+
+
+// The match itself: the rules, AI and physics, with no rendering, audio or windowing so it can
+// also be run (and stepped) headless with just `MinimalPlugins`
+pub struct PongSimulationPlugin {
+    pub arena: Arena,
+    pub seed: u64,
+}
+
+impl Plugin for PongSimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.arena)
+            .insert_resource(GameRng::new(self.seed))
+            .insert_resource(PlayerTurn(true))
+            .insert_resource(ServeOnDemand(false))
+            .insert_resource(InputMode::Mouse)
+            .insert_resource(ReplayMode::Off)
+            .insert_resource(Replay { seed: self.seed, ..default() })
+            .insert_resource(TickInput::default())
+            .insert_resource(ServePresses::default())
+            .insert_resource(InputLatency::default())
+            .insert_resource(KeyBindings::default())
+            .insert_resource(ActiveGamepad(None))
+            .insert_resource(DisconnectPause { enabled: true, waiting: false })
+            .insert_resource(TwoPlayer(false))
+            .insert_resource(MatchPoint(false))
+            .insert_resource(Endless { enabled: false, lives: ENDLESS_LIVES, survived: 0. })
+            .insert_resource(InputSources::default())
+            .insert_resource(InputAssignments::default())
+            .insert_resource(ServeRule::Alternate)
+            .insert_resource(RandomServe { enabled: false, max_angle: 0.35 })
+            .insert_resource(ServeSpawn::Center)
+            .insert_resource(BounceMode::Positional)
+            .insert_resource(GameSpeed(1.0))
+            .insert_resource(GameConfig::default())
+            .insert_resource(TimeScale(1.0))
+            .insert_resource(PhysicsClock::default())
+            .insert_resource(StepOnce(false))
+            .insert_resource(HitStop {
+                duration: HIT_STOP_DURATION,
+                remaining: finished_timer(MAX_HIT_STOP),
+            })
+            .insert_resource(PlayerTravelFraction(1.0))
+            .insert_resource(AiRestBias(0.0))
+            .insert_resource(AiSmoothing(0.0))
+            .insert_resource(Difficulty::Medium)
+            .insert_resource(OpponentTarget(None))
+            .insert_resource(AiHandicap {
+                initial_factor: 1.0,
+                ramp: Timer::from_seconds(3.0, false),
+            })
+            .insert_resource(StickyPaddle(false))
+            .insert_resource(StaminaEnabled(false))
+            .insert_resource(MagnetMode(false))
+            .insert_resource(TargetPractice(false))
+            .insert_resource(Heatmap::default())
+            .insert_resource(ReplayBuffer::new(REPLAY_SECONDS))
+            .insert_resource(SolidNet { enabled: false, gap: 160. })
+            .insert_resource(Onboarding { serves: 0, served: 0 })
+            .insert_resource(Scoreboard { player: 0, opponent: 0 })
+            .insert_resource(Series { player_matches: 0, opponent_matches: 0, best_of: 1 })
+            .insert_resource(WinningScore(11))
+            .insert_resource(WinByTwo(false))
+            .insert_resource(ComebackGap(COMEBACK_GAP))
+            .insert_resource(ClassicMode(false))
+            .insert_resource(TimedMatch {
+                enabled: false,
+                remaining: Timer::from_seconds(MATCH_DURATION, false),
+            })
+            .insert_resource(RallyCount(0))
+            .insert_resource(MatchStats::default())
+            .insert_resource(AttractMode {
+                idle: Timer::from_seconds(DEMO_IDLE_TIME, false),
+                saved: None,
+            })
+            .insert_resource(MultiBall {
+                enabled: false,
+                max_balls: MULTI_BALL_MAX,
+            })
+            .insert_resource(PracticeMode(false))
+            .insert_resource(PowerUps {
+                enabled: false,
+                spawn_timer: Timer::from_seconds(POWER_UP_SPAWN_INTERVAL, true),
+            })
+            .insert_resource(BallSpawnTimer(Timer::from_seconds(SERVE_COUNTDOWN, false)))
+            // Input read by the paddle controllers, already there (and kept up to date) if the
+            // InputPlugin was added, otherwise it just stays idle
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<Input<MouseButton>>()
+            .init_resource::<Input<GamepadButton>>()
+            .init_resource::<Axis<GamepadAxis>>()
+            .add_event::<MouseMotion>()
+            .add_event::<CollisionEvent>()
+            .add_event::<MatchEnded>()
+            .add_event::<RallyLet>()
+            .add_event::<Comeback>()
+            .add_state(AppState::Menu)
+            .add_system(fit_gameplay_to_arena)
+            .add_system(update_time_scale.before(apply_velocity))
+            .add_system(detect_match_point.after(check_game_over))
+            .add_system(assign_input_sources.before(read_tick_input))
+            .add_system_set(
+                SystemSet::on_enter(AppState::Playing)
+                    .with_system(start_replay)
+                    .with_system(setup_gameplay)
+                    .with_system(setup_magnet)
+                    .with_system(setup_solid_net)
+                    .with_system(reset_heatmap)
+                    .with_system(reset_replay_buffer)
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Playing)
+                    .with_system(latch_serve_presses)
+                    .with_system(timestamp_input.before(read_tick_input))
+                    .with_system(check_game_over)
+            )
+            .add_system_set(SystemSet::on_pause(AppState::Playing).with_system(record_pause))
+            .add_system_set(SystemSet::on_exit(AppState::Playing).with_system(despawn_with::<Gameplay>))
+            // The attract mode demo, an endless AI-vs-AI match pushed on top of the menu
+            .add_system_set(
+                SystemSet::on_enter(AppState::Demo)
+                    .with_system(start_demo)
+                    .with_system(setup_gameplay)
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Demo)
+                    .with_system(end_demo)
+                    .with_system(despawn_with::<Gameplay>)
+            )
+            .add_system_set(
+                    // Run physics systems (and anything that depends on physics systems) at constant FPS
+                SystemSet::new()
+                    .with_run_criteria(run_physics_tick)
+                    // Every system below reads its input from here, so a replay can stand in for it
+                    .with_system(
+                        read_tick_input
+                            .after(latch_serve_presses)
+                            .before(paddle_controller)
+                            .before(ball_spawner)
+                    )
+                    // Serves and power-ups draw on the seeded randomness, so they're timed in ticks too
+                    .with_system(ball_spawner.before(apply_velocity))
+                    .with_system(spawn_power_ups.before(apply_velocity))
+                    .with_system(restock_targets.after(process_collisions))
+                    .with_system(tick_match_clock)
+                    .with_system(paddle_controller.before(apply_velocity))
+                    .with_system(apply_magnets.before(apply_velocity))
+                    .with_system(apply_velocity)
+                    .with_system(
+                        sticky_paddle
+                            .after(apply_velocity)
+                            .before(process_collisions)
+                    )
+                    .with_system(
+                        process_collisions
+                            .after(paddle_controller)
+                            .after(apply_velocity)
+                    )
+                    .with_system(record_heatmap.after(process_collisions))
+                    .with_system(record_replay_frame.after(process_collisions))
+                    .with_system(stuck_detector.after(process_collisions))
+                    .with_system(grow_enlarged_paddles.after(process_collisions))
+                    .with_system(expire_enlarged_paddles.before(process_collisions))
+            );
+    }
+}
@@ -0,0 +1,1714 @@
+use bevy::audio::AudioSink;
+use bevy::diagnostic::DiagnosticId;
+use bevy::prelude::*;
+use bevy::utils::Instant;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::constants::*;
+
+
+// Top-level state of the app
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AppState {
+    Menu,
+    Settings,
+    KeyBindings,
+    Playing,
+    Paused,
+    GameOver,
+    // Attract mode: an AI-vs-AI match playing behind the title menu, pushed on top of Menu
+    Demo,
+}
+
+
+// Size of the play area, which fills the window
+#[derive(Clone, Copy)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Arena {
+    // Default arena, resized by any `--width <size>`/`--height <size>` (or `--width=<size>`) arguments
+    pub fn from_args(args: impl Iterator<Item = String>) -> Arena {
+        let mut arena = Arena { width: DEFAULT_ARENA_WIDTH, height: DEFAULT_ARENA_HEIGHT };
+
+        let mut args = args.skip(1);
+        while let Some(arg) = args.next() {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let size = match name.as_str() {
+                "--width" => &mut arena.width,
+                "--height" => &mut arena.height,
+                _ => continue,
+            };
+            match value.or_else(|| args.next()).map(|value| value.parse::<f32>()) {
+                Some(Ok(value)) if value >= MIN_ARENA_SIZE => *size = value,
+                _ => warn!("Ignoring {}: expected a size of at least {}", name, MIN_ARENA_SIZE),
+            }
+        }
+
+        arena
+    }
+}
+
+
+// Source of all the game's randomness, so the same seed (and the same inputs) plays out the exact
+// same match
+#[derive(Clone)]
+pub struct GameRng(pub StdRng);
+
+impl GameRng {
+    pub fn new(seed: u64) -> GameRng {
+        GameRng(StdRng::seed_from_u64(seed))
+    }
+
+    /// Seed given with --seed on the command line, or the default seed
+    pub fn seed_from_args(args: impl Iterator<Item = String>) -> u64 {
+        let mut seed = DEFAULT_SEED;
+
+        let mut args = args.skip(1);
+        while let Some(arg) = args.next() {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            if name != "--seed" {
+                continue;
+            }
+            match value.or_else(|| args.next()).map(|value| value.parse::<u64>()) {
+                Some(Ok(value)) => seed = value,
+                _ => warn!("Ignoring {}: expected a whole number", name),
+            }
+        }
+
+        seed
+    }
+}
+
+
+// Flag to determine which direction ball starts in
+pub struct PlayerTurn(pub bool);
+
+
+// Whether a human server launches the ball with a key press (Space, or Enter for the second player)
+// instead of it being served automatically after the countdown
+pub struct ServeOnDemand(pub bool);
+
+
+// How the player controls their paddle
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InputMode {
+    Mouse,
+    // Up/Down or W/S
+    Keyboard,
+}
+
+
+// What drives a paddle: a human on one of the input devices, the AI, or a remote player
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InputSource {
+    Mouse,
+    // Up/Down or W/S on the left paddle, the arrow keys on the right
+    Keyboard,
+    // A particular gamepad (on the left paddle, whichever is connected takes over while in use
+    // whatever its source)
+    Gamepad(Gamepad),
+    Ai,
+    // Nothing sends a remote player's input yet, so a networked paddle holds still. Only set by
+    // editing the settings file, the settings screen doesn't offer it
+    Network,
+}
+
+impl InputSource {
+    // Source after the given one (None for automatic) on the settings screen: automatic, each input
+    // device (the connected gamepads in order), then the AI, and round again. A source that's no
+    // longer on offer (e.g. a disconnected gamepad) goes back to automatic
+    pub fn cycle(current: Option<InputSource>, gamepads: &[Gamepad]) -> Option<InputSource> {
+        let mut choices = vec![None, Some(InputSource::Mouse), Some(InputSource::Keyboard)];
+        choices.extend(gamepads.iter().map(|&gamepad| Some(InputSource::Gamepad(gamepad))));
+        choices.push(Some(InputSource::Ai));
+        choices
+            .iter()
+            .position(|&choice| choice == current)
+            .and_then(|index| choices[(index + 1) % choices.len()])
+    }
+
+    // Name of the given source (None for automatic), as shown to the player
+    pub fn name(source: Option<InputSource>) -> String {
+        match source {
+            None => "Auto".to_string(),
+            Some(InputSource::Mouse) => "Mouse".to_string(),
+            Some(InputSource::Keyboard) => "Keyboard".to_string(),
+            Some(InputSource::Gamepad(gamepad)) => format!("Gamepad {}", gamepad.0 + 1),
+            Some(InputSource::Ai) => "AI".to_string(),
+            Some(InputSource::Network) => "Network".to_string(),
+        }
+    }
+}
+
+
+// The input source assigned to each paddle, kept in step with the input mode, gamepad and
+// two-player settings whenever one of them changes, unless the settings assign that paddle its own
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputSources {
+    pub player: InputSource,
+    pub opponent: InputSource,
+}
+
+impl Default for InputSources {
+    fn default() -> Self {
+        InputSources { player: InputSource::Mouse, opponent: InputSource::Ai }
+    }
+}
+
+
+// Input source chosen for each paddle in the settings, or None to go by the other input settings
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InputAssignments {
+    pub player: Option<InputSource>,
+    pub opponent: Option<InputSource>,
+}
+
+
+// Whether the player's input is recorded to the replay file each match, or played back from it in
+// place of live input (--record or --replay on the command line)
+#[derive(Clone, Copy, PartialEq)]
+pub enum ReplayMode {
+    Off,
+    Recording,
+    Playback,
+}
+
+impl ReplayMode {
+    /// Mode given with --record or --replay on the command line, otherwise off
+    pub fn from_args(args: impl Iterator<Item = String>) -> ReplayMode {
+        let mut mode = ReplayMode::Off;
+        for arg in args.skip(1) {
+            match arg.as_str() {
+                "--record" => mode = ReplayMode::Recording,
+                "--replay" => mode = ReplayMode::Playback,
+                _ => {},
+            }
+        }
+        mode
+    }
+}
+
+
+// Player input the simulation reads in a single physics tick
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TickInput {
+    pub mouse_delta_y: f32,
+    pub keyboard_direction: f32,
+    pub gamepad_direction: f32,
+    // Arrow keys, for the second player in two-player mode
+    pub second_player_direction: f32,
+    // Serve key pressed since the last tick, by the player or the second player
+    pub serve: bool,
+    pub second_player_serve: bool,
+    // Sticky paddle catch button held
+    pub catch: bool,
+}
+
+
+// Serve presses since the last physics tick, held until a tick reads them (a frame can run no ticks,
+// or several)
+#[derive(Default)]
+pub struct ServePresses {
+    pub player: bool,
+    pub second_player: bool,
+}
+
+
+// Delay from player input arriving (at the start of the frame it was read in) until the player
+// paddle moves from it, only measured while the debug overlay shows it
+#[derive(Default)]
+pub struct InputLatency {
+    pub measuring: bool,
+    pub input_at: Option<Instant>,
+    pub measured: Option<Duration>,
+}
+
+impl InputLatency {
+    pub const DIAGNOSTIC: DiagnosticId = DiagnosticId::from_u128(0x5d1e_42c7_9a3b_4f08_b6e1_2c9d_7a40_13f5);
+}
+
+
+// The player's input for every physics tick of a match, and the ticks it was paused after, along
+// with the seed and input mode it was played with, so the deterministic simulation plays it out
+// again the same way
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Replay {
+    pub seed: u64,
+    pub input_mode: InputMode,
+    pub inputs: Vec<TickInput>,
+    pub pauses: Vec<usize>,
+    // Next input, and next pause, to play back
+    #[serde(skip)]
+    pub cursor: usize,
+    #[serde(skip)]
+    pub next_pause: usize,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Replay {
+            seed: DEFAULT_SEED,
+            input_mode: InputMode::Mouse,
+            inputs: Vec::new(),
+            pauses: Vec::new(),
+            cursor: 0,
+            next_pause: 0,
+        }
+    }
+}
+
+impl Replay {
+    /// Input to use this tick: the next recorded input when playing back (going back to live input
+    /// once the recording runs out), otherwise the live input, added to the recording if recording
+    pub fn next_input(&mut self, mode: ReplayMode, live: TickInput) -> TickInput {
+        match mode {
+            ReplayMode::Off => live,
+            ReplayMode::Recording => {
+                self.inputs.push(live);
+                live
+            },
+            ReplayMode::Playback => {
+                let input = self.inputs.get(self.cursor).copied().unwrap_or(live);
+                self.cursor += 1;
+                input
+            },
+        }
+    }
+
+    /// Whether playback has reached a point the recording was paused at (each pause only once)
+    pub fn take_pause(&mut self) -> bool {
+        let due = self.pauses.get(self.next_pause) == Some(&self.cursor);
+        if due {
+            self.next_pause += 1;
+        }
+        due
+    }
+}
+
+
+// Where everything was on one physics tick, as held in the replay buffer
+#[derive(Clone, Default)]
+pub struct ReplayFrame {
+    pub player_y: f32,
+    pub opponent_y: f32,
+    pub balls: Vec<Vec2>,
+}
+
+
+// Rolling buffer of the last `replay_seconds` of play, one frame per physics tick, oldest first, so
+// the end of a rally can be replayed on demand (while paused). Memory grows with the length: each frame is around
+// 40 bytes plus 8 per ball in play, so the 10 second maximum holds 600 frames (roughly 30 KB even with
+// multi-ball)
+pub struct ReplayBuffer {
+    replay_seconds: f32,
+    pub frames: VecDeque<ReplayFrame>,
+}
+
+impl ReplayBuffer {
+    pub fn new(replay_seconds: f32) -> Self {
+        let replay_seconds = replay_seconds.clamp(MIN_REPLAY_SECONDS, MAX_REPLAY_SECONDS);
+        let mut buffer = ReplayBuffer { replay_seconds, frames: VecDeque::new() };
+        buffer.frames.reserve_exact(buffer.capacity());
+        buffer
+    }
+
+    pub fn replay_seconds(&self) -> f32 {
+        self.replay_seconds
+    }
+
+    // Frames held at most, from the physics rate
+    pub fn capacity(&self) -> usize {
+        (self.replay_seconds / TIME_STEP).round() as usize
+    }
+
+    // Change how much play is held, keeping as many of the latest ticks as still fit
+    pub fn set_replay_seconds(&mut self, replay_seconds: f32) {
+        self.replay_seconds = replay_seconds.clamp(MIN_REPLAY_SECONDS, MAX_REPLAY_SECONDS);
+        while self.frames.len() > self.capacity() {
+            self.frames.pop_front();
+        }
+    }
+
+    // Add the latest tick, dropping the oldest ones once full
+    pub fn push(&mut self, frame: ReplayFrame) {
+        while self.frames.len() >= self.capacity() {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    // Frame shown the given time into playing back the buffer, if it hasn't run out by then
+    pub fn frame_at(&self, seconds: f32) -> Option<&ReplayFrame> {
+        self.frames.get((seconds / TIME_STEP) as usize)
+    }
+}
+
+
+// Time into playing back the replay buffer, while a replay is showing (started with R while paused)
+pub struct ReplayPlayback(pub Option<f32>);
+
+
+// Gamepad driving the player paddle, if one is connected
+pub struct ActiveGamepad(pub Option<Gamepad>);
+
+
+// Optionally pauses the match when the player's gamepad disconnects, `waiting` until a gamepad
+// reconnects or the player switches input mode to carry on with mouse or keyboard
+pub struct DisconnectPause {
+    pub enabled: bool,
+    pub waiting: bool,
+}
+
+
+// Local two-player mode: a second human drives the right paddle with the arrow keys
+// (player one is then on W/S) instead of the AI
+pub struct TwoPlayer(pub bool);
+
+
+// Optionally serves the ball at a random angle, up to `max_angle` (radians) either side of straight
+// across, so serves aren't all identical
+pub struct RandomServe {
+    pub enabled: bool,
+    pub max_angle: f32,
+}
+
+
+// Where serves start from: the middle of the arena, or for practice drills the top and bottom
+// corners of the serving side in turn (`top` next), angled back into the arena (set in the settings)
+#[derive(Clone, Copy, PartialEq)]
+pub enum ServeSpawn {
+    Center,
+    Corners { top: bool },
+}
+
+
+// Rule for which direction the ball is served after a goal
+#[derive(PartialEq)]
+pub enum ServeRule {
+    // Serve direction alternates every point
+    Alternate,
+    // Whoever scored serves next (ball heads toward the non-scorer)
+    ScorerServes,
+}
+
+
+// Model for how the ball leaves a paddle
+#[derive(PartialEq)]
+pub enum BounceMode {
+    // Angle is steered by where on the paddle the ball hits
+    Positional,
+    // Mirror reflection, preserving the incoming angle (and speed)
+    Reflect,
+}
+
+
+// Overall tempo multiplier applied to ball and AI speeds (1.0 = normal pace)
+pub struct GameSpeed(pub f32);
+
+
+// Gameplay tuning, read by the systems as they run so it can be changed at any time (sizes take
+// effect as things are spawned)
+// Defaults come from the constants, which also keep anything that can't safely change mid-game,
+// like the physics framerate.
+#[derive(Clone, Copy)]
+pub struct GameConfig {
+    pub paddle_size: Vec2,
+    pub ball_size: Vec2,
+    pub ball_speed: f32,
+    pub max_ball_speed: f32,
+    pub bounce_angle_multiplier: f32,
+    pub max_bounce_y_speed: f32,
+    pub paddle_spin_factor: f32,
+    pub rally_speed_ramp: f32,
+    pub rally_max_speed_factor: f32,
+    pub max_rally_length: Option<u32>,
+    pub ball_shrink_per_hit: f32,
+    pub min_ball_scale: f32,
+    pub player_paddle_speed: f32,
+    pub paddle_max_accel: f32,
+    pub paddle_friction: f32,
+    pub ai_rest_tracking: f32,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            paddle_size: PADDLE_SIZE,
+            ball_size: BALL_SIZE,
+            ball_speed: BALL_SPEED,
+            max_ball_speed: MAX_BALL_SPEED,
+            bounce_angle_multiplier: BOUNCE_ANGLE_MULTIPLIER,
+            max_bounce_y_speed: MAX_BOUNCE_Y_SPEED,
+            paddle_spin_factor: PADDLE_SPIN_FACTOR,
+            rally_speed_ramp: RALLY_SPEED_RAMP,
+            rally_max_speed_factor: RALLY_MAX_SPEED_FACTOR,
+            max_rally_length: MAX_RALLY_LENGTH,
+            ball_shrink_per_hit: BALL_SHRINK_PER_HIT,
+            min_ball_scale: MIN_BALL_SCALE,
+            player_paddle_speed: PLAYER_PADDLE_SPEED,
+            paddle_max_accel: PADDLE_MAX_ACCEL,
+            paddle_friction: PADDLE_FRICTION,
+            ai_rest_tracking: AI_REST_TRACKING,
+        }
+    }
+}
+
+
+// Slow motion multiplier on how far things move each physics tick (1.0 = real time)
+// Scales movement rather than the fixed timestep, so physics keeps running at a constant rate.
+pub struct TimeScale(pub f32);
+
+
+// Whether the next goal could win the match, for either side (overtime included, playing win by two)
+pub struct MatchPoint(pub bool);
+
+
+// Optionally heightens match point with a pulsing border, a tense music layer and a banner
+pub struct MatchPointEmphasis(pub bool);
+
+
+// Clock driving the fixed timestep physics, banking real time while a match is on and spending it a
+// tick at a time. When `manual`, real time is ignored and only queued steps run, so the simulation
+// can be stepped exactly (e.g. headless, in tests)
+#[derive(Default)]
+pub struct PhysicsClock {
+    pub accumulator: f64,
+    pub manual: bool,
+    pub queued_steps: u32,
+    // Whether ticks are already being run this frame, so time isn't banked again for each one
+    pub looping: bool,
+}
+
+
+// Run a single physics tick while paused (Period, in debug builds), to step through collisions
+pub struct StepOnce(pub bool);
+
+
+// Hit-stop: physics freezes for `duration` seconds (at most MAX_HIT_STOP, 0 = off) on each paddle
+// hit, so hard returns feel weighty. Ticks that come due while `remaining` runs are skipped
+pub struct HitStop {
+    pub duration: f32,
+    pub remaining: Timer,
+}
+
+
+// Fraction of the arena height the player paddle may travel (1.0 = full height)
+pub struct PlayerTravelFraction(pub f32);
+
+
+// Y offset from center the opponent drifts to between rallies (its "favored side")
+pub struct AiRestBias(pub f32);
+
+
+// How much the opponent's velocity lags behind its target each tick
+// (0.0 = instant, approaching 1.0 = very smooth/laggy)
+pub struct AiSmoothing(pub f32);
+
+
+// How well the AI opponent plays, selectable at any time with the 1/2/3 keys
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    // Gain applied to the Y distance to the ball when tracking it
+    pub fn tracking_gain(self) -> f32 {
+        match self {
+            Difficulty::Easy => 6.,
+            Difficulty::Medium => 13.,
+            Difficulty::Hard => 30.,
+        }
+    }
+
+    pub fn max_speed(self) -> f32 {
+        match self {
+            Difficulty::Easy => 300.,
+            Difficulty::Medium => 450.,
+            Difficulty::Hard => 700.,
+        }
+    }
+
+    // Minimum velocity smoothing, so easier opponents react to the ball later
+    pub fn reaction_lag(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.8,
+            Difficulty::Medium => 0.,
+            Difficulty::Hard => 0.,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    // Seconds behind the ball the opponent sees it, as if reacting to where it was that long ago
+    pub fn reaction_time(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.25,
+            Difficulty::Medium => 0.1,
+            Difficulty::Hard => 0.02,
+        }
+    }
+
+    // Whether the opponent heads for where the ball will arrive, rather than just chasing it
+    pub fn predicts_ball(self) -> bool {
+        self != Difficulty::Easy
+    }
+}
+
+
+// Keyboard controls that can be rebound from the key bindings screen
+#[derive(Clone, Copy, PartialEq)]
+pub enum KeyAction {
+    MoveUp,
+    MoveDown,
+    Pause,
+    Serve,
+    Mute,
+    ToggleHeatmap,
+    ClearHeatmap,
+    InstantReplay,
+}
+
+impl KeyAction {
+    pub const ALL: [KeyAction; 8] = [
+        KeyAction::MoveUp,
+        KeyAction::MoveDown,
+        KeyAction::Pause,
+        KeyAction::Serve,
+        KeyAction::Mute,
+        KeyAction::ToggleHeatmap,
+        KeyAction::ClearHeatmap,
+        KeyAction::InstantReplay,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyAction::MoveUp => "Move Up",
+            KeyAction::MoveDown => "Move Down",
+            KeyAction::Pause => "Pause",
+            KeyAction::Serve => "Serve",
+            KeyAction::Mute => "Mute",
+            KeyAction::ToggleHeatmap => "Heatmap",
+            KeyAction::ClearHeatmap => "Clear Heatmap",
+            KeyAction::InstantReplay => "Instant Replay",
+        }
+    }
+}
+
+
+// Key bound to each rebindable action (player one's, in two-player mode), persisted with the
+// settings
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub move_up: KeyCode,
+    pub move_down: KeyCode,
+    pub pause: KeyCode,
+    pub serve: KeyCode,
+    pub mute: KeyCode,
+    pub toggle_heatmap: KeyCode,
+    pub clear_heatmap: KeyCode,
+    pub instant_replay: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            move_up: KeyCode::W,
+            move_down: KeyCode::S,
+            pause: KeyCode::P,
+            serve: KeyCode::Space,
+            mute: KeyCode::M,
+            toggle_heatmap: KeyCode::H,
+            clear_heatmap: KeyCode::C,
+            instant_replay: KeyCode::R,
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn key(&self, action: KeyAction) -> KeyCode {
+        match action {
+            KeyAction::MoveUp => self.move_up,
+            KeyAction::MoveDown => self.move_down,
+            KeyAction::Pause => self.pause,
+            KeyAction::Serve => self.serve,
+            KeyAction::Mute => self.mute,
+            KeyAction::ToggleHeatmap => self.toggle_heatmap,
+            KeyAction::ClearHeatmap => self.clear_heatmap,
+            KeyAction::InstantReplay => self.instant_replay,
+        }
+    }
+
+    // Bind the key to the action, unless it's already bound to another action.
+    // Returns whether it was bound.
+    pub fn bind(&mut self, action: KeyAction, key: KeyCode) -> bool {
+        if KeyAction::ALL.iter().any(|&other| other != action && self.key(other) == key) {
+            return false;
+        }
+        let bound_key = match action {
+            KeyAction::MoveUp => &mut self.move_up,
+            KeyAction::MoveDown => &mut self.move_down,
+            KeyAction::Pause => &mut self.pause,
+            KeyAction::Serve => &mut self.serve,
+            KeyAction::Mute => &mut self.mute,
+            KeyAction::ToggleHeatmap => &mut self.toggle_heatmap,
+            KeyAction::ClearHeatmap => &mut self.clear_heatmap,
+            KeyAction::InstantReplay => &mut self.instant_replay,
+        };
+        *bound_key = key;
+        true
+    }
+}
+
+
+// Name of a key, as shown to the player
+pub fn key_name(key: KeyCode) -> String {
+    match key {
+        KeyCode::Return => "Enter".to_string(),
+        KeyCode::Back => "Backspace".to_string(),
+        _ => format!("{:?}", key),
+    }
+}
+
+
+// Action on the key bindings screen waiting for the next key press to bind to it, if any
+pub struct Rebinding(pub Option<KeyAction>);
+
+
+// Named color scheme, selectable from the settings screen or at any time with T
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ThemePreset {
+    Classic,
+    Amber,
+    GreenPhosphor,
+    HighContrast,
+}
+
+impl ThemePreset {
+    pub fn next(self) -> ThemePreset {
+        match self {
+            ThemePreset::Classic => ThemePreset::Amber,
+            ThemePreset::Amber => ThemePreset::GreenPhosphor,
+            ThemePreset::GreenPhosphor => ThemePreset::HighContrast,
+            ThemePreset::HighContrast => ThemePreset::Classic,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ThemePreset::Classic => "Classic",
+            ThemePreset::Amber => "Amber",
+            ThemePreset::GreenPhosphor => "Green Phosphor",
+            ThemePreset::HighContrast => "High Contrast",
+        }
+    }
+}
+
+
+// Colors the game is drawn in, from the selected preset
+// (the ball shifts from `ball` toward `ball_fast` as it speeds up), optionally with each side's
+// paddle tinted its own colorblind-safe color instead of the preset's
+pub struct Theme {
+    pub preset: ThemePreset,
+    pub distinct_paddles: bool,
+    pub background: Color,
+    pub paddle: Color,
+    pub ball: Color,
+    pub ball_fast: Color,
+    pub net: Color,
+    pub text: Color,
+}
+
+impl Theme {
+    pub fn from_preset(preset: ThemePreset) -> Theme {
+        match preset {
+            ThemePreset::Classic => Theme {
+                preset,
+                distinct_paddles: false,
+                background: Color::BLACK,
+                paddle: Color::WHITE,
+                ball: Color::WHITE,
+                ball_fast: Color::rgb(1.0, 0.2, 0.2),
+                net: Color::rgb(0.65, 0.65, 0.65),
+                text: Color::WHITE,
+            },
+            ThemePreset::Amber => Theme {
+                preset,
+                distinct_paddles: false,
+                background: Color::rgb(0.08, 0.05, 0.0),
+                paddle: Color::rgb(1.0, 0.69, 0.0),
+                ball: Color::rgb(1.0, 0.69, 0.0),
+                ball_fast: Color::rgb(1.0, 0.25, 0.0),
+                net: Color::rgb(0.6, 0.4, 0.0),
+                text: Color::rgb(1.0, 0.69, 0.0),
+            },
+            ThemePreset::GreenPhosphor => Theme {
+                preset,
+                distinct_paddles: false,
+                background: Color::rgb(0.0, 0.06, 0.02),
+                paddle: Color::rgb(0.2, 1.0, 0.3),
+                ball: Color::rgb(0.2, 1.0, 0.3),
+                ball_fast: Color::rgb(0.85, 1.0, 0.85),
+                net: Color::rgb(0.1, 0.55, 0.15),
+                text: Color::rgb(0.2, 1.0, 0.3),
+            },
+            ThemePreset::HighContrast => Theme {
+                preset,
+                distinct_paddles: false,
+                background: Color::BLACK,
+                paddle: Color::YELLOW,
+                ball: Color::WHITE,
+                ball_fast: Color::RED,
+                net: Color::WHITE,
+                text: Color::WHITE,
+            },
+        }
+    }
+
+    // Switch to another preset, keeping the paddle tinting option
+    pub fn with_preset(&self, preset: ThemePreset) -> Theme {
+        Theme {
+            distinct_paddles: self.distinct_paddles,
+            ..Theme::from_preset(preset)
+        }
+    }
+
+    pub fn player_paddle(&self) -> Color {
+        if self.distinct_paddles { PLAYER_PADDLE_TINT } else { self.paddle }
+    }
+
+    pub fn opponent_paddle(&self) -> Color {
+        if self.distinct_paddles { OPPONENT_PADDLE_TINT } else { self.paddle }
+    }
+}
+
+
+// Y position the AI opponent is currently heading for, if it's playing
+pub struct OpponentTarget(pub Option<f32>);
+
+
+// Per-rally handicap: the AI's tracking gain starts each rally scaled by `initial_factor`,
+// ramping up to full over the duration of `ramp` (an initial factor of 1.0 disables it)
+pub struct AiHandicap {
+    pub initial_factor: f32,
+    pub ramp: Timer,
+}
+
+
+// Enables catching the ball by holding the left mouse button as it hits the player paddle
+pub struct StickyPaddle(pub bool);
+
+
+// Player preference multiplying UI font sizes, margins and menu button sizes (1.0 = default size)
+pub struct UiScale(pub f32);
+
+impl UiScale {
+    /// The scale clamped to a usable range
+    pub fn value(&self) -> f32 {
+        self.0.clamp(0.5, 2.0)
+    }
+
+    /// The next larger of the preset scales, back round to the smallest after the largest
+    pub fn next(&self) -> UiScale {
+        let next = UI_SCALE_PRESETS.iter().find(|&&preset| preset > self.0 + 0.01);
+        UiScale(*next.unwrap_or(&UI_SCALE_PRESETS[0]))
+    }
+}
+
+
+// Enables the paddle stamina mechanic, where frantic movement tires the paddle out
+pub struct StaminaEnabled(pub bool);
+
+
+// Enables the magnet obstacle mode, where a magnet near the center curves the ball's path
+pub struct MagnetMode(pub bool);
+
+
+// Enables target practice, where targets in the opponent's half are knocked out by the ball, set out
+// again once they're all gone
+pub struct TargetPractice(pub bool);
+
+
+// Optionally makes the net a solid wall that balls bounce off, except through a central gap
+pub struct SolidNet {
+    pub enabled: bool,
+    pub gap: f32,
+}
+
+
+// Onboarding assist: the first `serves` serves of a session are slowed down, ramping up to
+// full speed (ends early once the player scores)
+#[derive(Clone)]
+pub struct Onboarding {
+    pub serves: u16,
+    pub served: u16,
+}
+
+impl Onboarding {
+    /// Serve speed multiplier for the next serve
+    pub fn serve_speed_factor(&self) -> f32 {
+        if self.served >= self.serves {
+            return 1.;
+        }
+        let progress = self.served as f32 / self.serves as f32;
+        ONBOARDING_SERVE_SPEED + (1. - ONBOARDING_SERVE_SPEED) * progress
+    }
+}
+
+
+// Histogram of where the ball has spent its time, with a toggleable overlay
+#[derive(Clone)]
+pub struct Heatmap {
+    pub counts: Vec<u32>,
+    pub visible: bool,
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; HEATMAP_COLUMNS * HEATMAP_ROWS],
+            visible: false,
+        }
+    }
+}
+
+
+// Number of paddle hits in the current rally
+pub struct RallyCount(pub u32);
+
+
+// Running totals for the current match, shown on the game over screen
+#[derive(Clone, Default)]
+pub struct MatchStats {
+    // Rallies played to a finish
+    pub rallies: u32,
+    pub longest_rally: u32,
+    pub wall_bounces: u32,
+    pub fastest_ball_speed: f32,
+    // Seconds played, not counting pauses
+    pub duration: f32,
+}
+
+impl MatchStats {
+    // Row of the match stats CSV for a finished match, in the order of MATCH_STATS_HEADER
+    pub fn csv_row(&self, date: &str, mode: &str, difficulty: Difficulty, score: &Scoreboard) -> String {
+        format!(
+            "{},{},{},{},{},{:.0},{}",
+            date, mode, difficulty.name(), score.player, score.opponent, self.duration, self.longest_rally,
+        )
+    }
+}
+
+
+// Practice mode, where the right gutter sends the ball back into play instead of the player
+// scoring, so rallies keep going (the opponent still scores as usual)
+pub struct PracticeMode(pub bool);
+
+
+// Multi-ball mode: extra balls join long rallies, each scoring independently, up to max_balls
+// in play at once
+pub struct MultiBall {
+    pub enabled: bool,
+    pub max_balls: usize,
+}
+
+
+// Whether the speed tint also applies to balls with their own multi-ball color (tinting from that
+// color toward the theme's fast ball color), rather than their own color always showing as is
+pub struct SpeedTintBallColors(pub bool);
+
+
+// Draw a fading trail behind the ball (can be turned off on low-end machines)
+pub struct BallTrail(pub bool);
+
+
+// Classic mode (--classic on the command line): essentially the original Pong, with every modern
+// extra turned off at startup. Paddle sparks and camera punches have no setting of their own, so
+// they check this directly
+#[derive(Clone, Copy)]
+pub struct ClassicMode(pub bool);
+
+impl ClassicMode {
+    /// On if --classic was given on the command line
+    pub fn from_args(args: impl Iterator<Item = String>) -> ClassicMode {
+        ClassicMode(args.skip(1).any(|arg| arg == "--classic"))
+    }
+}
+
+
+// Suppress purely visual motion (ball trails, score pops, camera punches, sparks) for players
+// sensitive to it, without changing how the game plays
+pub struct ReduceMotion(pub bool);
+
+
+// Ease the camera back to center (and its zoom back to fitting the arena) over each serve
+// countdown, so every rally starts from a neutral view
+pub struct CameraRecenter(pub bool);
+
+
+// Optionally spawns power-ups around the play field at a regular interval
+pub struct PowerUps {
+    pub enabled: bool,
+    pub spawn_timer: Timer,
+}
+
+
+// Timer to determine time between ball spawns
+pub struct BallSpawnTimer(pub Timer);
+
+
+#[derive(Clone)]
+pub struct Scoreboard {
+    pub player: u16,
+    pub opponent: u16,
+}
+
+
+// Running totals for the session, over every match since leaving the main menu, shown on the game
+// over screen alongside the match's own
+#[derive(Clone, Default)]
+pub struct SessionStats {
+    pub matches: u32,
+    pub rallies: u32,
+    pub longest_rally: u32,
+    // Seconds played, not counting pauses
+    pub duration: f32,
+}
+
+impl SessionStats {
+    // Fold a finished match's stats into the session's
+    pub fn add_match(&mut self, match_stats: &MatchStats) {
+        self.matches += 1;
+        self.rallies += match_stats.rallies;
+        self.longest_rally = self.longest_rally.max(match_stats.longest_rally);
+        self.duration += match_stats.duration;
+    }
+}
+
+
+// Optionally carry the session stats over a rematch, otherwise each rematch starts a new session
+pub struct CarrySessionStats(pub bool);
+
+
+// Matches won by each side in a best-of-N series (a best of 1 is just a single match)
+pub struct Series {
+    pub player_matches: u8,
+    pub opponent_matches: u8,
+    pub best_of: u8,
+}
+
+impl Series {
+    /// Matches a side needs to win to take the series
+    pub fn matches_to_win(&self) -> u8 {
+        self.best_of / 2 + 1
+    }
+}
+
+
+// Attract mode: how long the title menu has sat idle, and the match state put aside while the demo
+// plays (restored when it ends, so the demo never carries over into a real game)
+pub struct AttractMode {
+    pub idle: Timer,
+    pub saved: Option<DemoSnapshot>,
+}
+
+pub struct DemoSnapshot {
+    pub scoreboard: Scoreboard,
+    pub rally_count: u32,
+    pub stats: MatchStats,
+    pub player_turn: bool,
+    pub ball_spawn_timer: Timer,
+    pub match_clock: Timer,
+    pub onboarding: Onboarding,
+    pub heatmap: Heatmap,
+    pub rng: GameRng,
+    pub two_player: bool,
+    pub serve_on_demand: bool,
+    pub endless: Endless,
+    pub serve_spawn: ServeSpawn,
+}
+
+
+// Player preferences, persisted across runs as a single file and applied to their own resources
+#[derive(PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub master_volume: f32,
+    pub muted: bool,
+    pub music_enabled: bool,
+    pub difficulty: Difficulty,
+    pub input_mode: InputMode,
+    pub theme: ThemePreset,
+    pub distinct_paddles: bool,
+    pub reduce_motion: bool,
+    pub corner_serves: bool,
+    pub player_input: Option<InputSource>,
+    pub opponent_input: Option<InputSource>,
+    pub key_bindings: KeyBindings,
+    pub replay_seconds: f32,
+    pub ui_scale: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            master_volume: 1.0,
+            muted: false,
+            music_enabled: true,
+            difficulty: Difficulty::Medium,
+            input_mode: InputMode::Mouse,
+            theme: ThemePreset::Classic,
+            distinct_paddles: false,
+            reduce_motion: false,
+            corner_serves: false,
+            player_input: None,
+            opponent_input: None,
+            key_bindings: KeyBindings::default(),
+            replay_seconds: REPLAY_SECONDS,
+            ui_scale: 1.0,
+        }
+    }
+}
+
+
+// Personal bests, persisted across runs
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Records {
+    pub longest_rally: u32,
+    // Most goals the player has scored in a single match
+    pub most_goals: u16,
+}
+
+
+// A rally on the leaderboard: how many hits it lasted, and the day (YYYY-MM-DD) it was played
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RallyRecord {
+    pub count: u32,
+    pub date: String,
+}
+
+
+// Longest rallies ever played, longest first, persisted across runs
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RallyLeaderboard {
+    pub rallies: Vec<RallyRecord>,
+}
+
+impl RallyLeaderboard {
+    // Add a finished rally if it makes the top RALLY_LEADERBOARD_SIZE, returning whether it did.
+    // A rally tying one already on the board goes below it
+    pub fn record(&mut self, count: u32, date: &str) -> bool {
+        let position = self
+            .rallies
+            .iter()
+            .position(|rally| rally.count < count)
+            .unwrap_or(self.rallies.len());
+        if count == 0 || position >= RALLY_LEADERBOARD_SIZE {
+            return false;
+        }
+        self.rallies.insert(position, RallyRecord { count, date: date.to_string() });
+        self.rallies.truncate(RALLY_LEADERBOARD_SIZE);
+        true
+    }
+}
+
+
+// Optionally plays for survival rather than to a winning score: the AI gets faster and sharper, and
+// serves faster, the longer the player lasts, until they've conceded `lives` goals
+#[derive(Clone)]
+pub struct Endless {
+    pub enabled: bool,
+    pub lives: u16,
+    // Seconds of play survived so far
+    pub survived: f32,
+}
+
+impl Endless {
+    // How far the match has ramped up toward its hardest, from 0 to 1
+    pub fn ramp(&self) -> f32 {
+        if self.enabled {
+            (self.survived / ENDLESS_RAMP_TIME).min(1.)
+        } else {
+            0.
+        }
+    }
+
+    // Multiplier on the AI's tracking and top speed, and divisor on its reaction time
+    pub fn ai_boost(&self) -> f32 {
+        1. + self.ramp() * (ENDLESS_MAX_AI_BOOST - 1.)
+    }
+
+    // Multiplier on the serve speed
+    pub fn serve_boost(&self) -> f32 {
+        1. + self.ramp() * (ENDLESS_MAX_SERVE_BOOST - 1.)
+    }
+
+    // Goals the player can still concede before it's over
+    pub fn lives_left(&self, scoreboard: &Scoreboard) -> u16 {
+        self.lives.saturating_sub(scoreboard.opponent)
+    }
+}
+
+
+// Score a side needs to reach to win the match
+pub struct WinningScore(pub u16);
+
+
+// Table tennis rule: a side must also lead by two to win, so from deuce play goes on until one does
+pub struct WinByTwo(pub bool);
+
+
+// Optionally also ends the match when the clock runs out, won by whoever is ahead (or by the next
+// goal if the score is tied)
+pub struct TimedMatch {
+    pub enabled: bool,
+    pub remaining: Timer,
+}
+
+
+// What a menu button does when it's clicked, or Enter is pressed while it has focus
+#[derive(Clone, Copy, PartialEq)]
+pub enum MenuAction {
+    Play,
+    PlayEndless,
+    OpenSettings,
+    Quit,
+    CycleDifficulty,
+    ToggleInputMode,
+    CyclePlayerInput,
+    CycleOpponentInput,
+    ToggleMute,
+    ToggleMusic,
+    CycleTheme,
+    ToggleDistinctPaddles,
+    ToggleReduceMotion,
+    CycleReplaySeconds,
+    ToggleCornerServes,
+    CycleUiScale,
+    OpenKeyBindings,
+    Rebind(KeyAction),
+    Back,
+    Rematch,
+    MainMenu,
+}
+
+
+// Index of the menu button with keyboard focus
+pub struct MenuFocus(pub usize);
+
+
+// A match (of the series) has been won, with its final score and stats
+pub struct MatchEnded(pub Scoreboard, pub MatchStats);
+
+
+// Append each finished match's stats to the match stats CSV
+pub struct ExportMatchStats(pub bool);
+
+
+// A rally went on past the max rally length and was called off, with no point scored
+pub struct RallyLet;
+
+
+// Deficit (in goals) a side must be scoring from for the goal to count as a comeback, or 0 for none
+pub struct ComebackGap(pub u16);
+
+impl ComebackGap {
+    // Whether a goal scored with the scorer and their opponent on the given scores (before the goal)
+    // is a comeback
+    pub fn is_comeback(&self, scorer: u16, opponent: u16) -> bool {
+        self.0 > 0 && opponent >= scorer + self.0
+    }
+}
+
+
+// A side scored from far enough behind to be a comeback
+pub struct Comeback;
+
+
+pub enum CollisionEvent {
+    // Off a wall or obstacle
+    WallBounce,
+    // Where the ball met the paddle
+    PaddleBounce(Vec2),
+    Goal,
+}
+
+
+pub struct HitSound(pub Handle<AudioSource>);
+
+
+pub struct GoalSound(pub Handle<AudioSource>);
+
+
+pub struct ComebackSound(pub Handle<AudioSource>);
+
+
+pub struct WallSound(pub Handle<AudioSource>);
+
+
+// Overall volume applied to all music and sounds, whether they're muted (M), and whether music plays
+pub struct AudioSettings {
+    pub master_volume: f32,
+    pub muted: bool,
+    pub music_enabled: bool,
+}
+
+impl AudioSettings {
+    // Effective volume for a sound or music with the given base volume
+    pub fn volume(&self, volume: f32) -> f32 {
+        if self.muted {
+            0.
+        } else {
+            volume * self.master_volume.clamp(0., 1.)
+        }
+    }
+
+    // Effective volume for music with the given base volume, silent if music is turned off
+    pub fn music_volume(&self, volume: f32) -> f32 {
+        if self.music_enabled {
+            self.volume(volume)
+        } else {
+            0.
+        }
+    }
+}
+
+
+pub struct ServeChargeSound(pub Handle<AudioSource>);
+
+
+// Optionally plays a rising "charge-up" sound over the end of the serve countdown, telegraphing the
+// launch. Started once per countdown, with its playback kept so an early serve can cut it off
+pub struct ServeCharge {
+    pub enabled: bool,
+    pub started: bool,
+    pub sink: Option<Handle<AudioSink>>,
+}
+
+impl ServeCharge {
+    // Whether to start the sound now, given the serve timer: once per countdown, as it enters its
+    // final stretch, and never once it's over (including when the serve was made early)
+    pub fn should_start(&mut self, ball_spawn_timer: &Timer) -> bool {
+        let remaining = ball_spawn_timer.duration().as_secs_f32() - ball_spawn_timer.elapsed_secs();
+        if ball_spawn_timer.finished() {
+            return false;
+        }
+        if remaining > SERVE_CHARGE_DURATION {
+            self.started = false;
+            return false;
+        }
+        let start = self.enabled && !self.started;
+        self.started = true;
+        start
+    }
+}
+
+
+// Strong handle to the looping music, used to control its volume
+pub struct MusicSink(pub Handle<AudioSink>);
+
+
+// Strong handle to the looping tense layer, silent until match point
+pub struct MatchPointMusicSink(pub Handle<AudioSink>);
+
+
+// Strong handles to the looping music stems, in the order they come in, silent until the score builds.
+// A stem that failed to load never gets a sink, so it's simply left out
+pub struct MusicStems {
+    pub enabled: bool,
+    pub sinks: Vec<Handle<AudioSink>>,
+}
+
+impl MusicStems {
+    // How far the match has built towards its climax, from 0 at the start to 1 once either side is a
+    // point from the winning score
+    pub fn intensity(scoreboard: &Scoreboard, winning_score: &WinningScore) -> f32 {
+        let leading = scoreboard.player.max(scoreboard.opponent) as f32;
+        (leading / winning_score.0.saturating_sub(1).max(1) as f32).min(1.)
+    }
+
+    // Volume of the stem at the given index at the given intensity, each stem fading in over its own
+    // share of the intensity range after the ones before it
+    pub fn stem_volume(&self, index: usize, intensity: f32, audio_settings: &AudioSettings) -> f32 {
+        if !self.enabled {
+            return 0.;
+        }
+        let fade = (intensity * MUSIC_STEMS.len() as f32 - index as f32).clamp(0., 1.);
+        audio_settings.music_volume(MUSIC_STEM_VOLUME * fade)
+    }
+}
+
+
+// Timer for temporarily lowering the music volume while the goal sound plays
+pub struct MusicDuck(pub Timer);
+
+impl MusicDuck {
+    // Music volume under the audio settings, lowered the most right after a goal and easing back up
+    pub fn music_volume(&self, audio_settings: &AudioSettings) -> f32 {
+        let duck = MUSIC_DUCK_AMOUNT * self.0.percent_left();
+        audio_settings.music_volume(MUSIC_VOLUME * (1. - duck))
+    }
+}
+
+
+/// Path of a file in the game's data directory, if the platform has one (e.g. not on the web)
+fn data_file_path(file_name: &str) -> Option<PathBuf> {
+    let data_dir = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    };
+    data_dir.map(|data_dir| data_dir.join("bevy-pong").join(file_name))
+}
+
+
+/// Load a RON file from the data directory, falling back to defaults if it's missing or corrupt
+pub fn load_data_file<T: DeserializeOwned + Default>(file_name: &str) -> T {
+    let path = match data_file_path(file_name) {
+        Some(path) => path,
+        None => return T::default(),
+    };
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        // Nothing saved yet
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return T::default(),
+        Err(error) => {
+            warn!("Couldn't read {}, using defaults: {}", path.display(), error);
+            return T::default();
+        },
+    };
+    ron::from_str(&contents).unwrap_or_else(|error| {
+        warn!("Couldn't parse {}, using defaults: {}", path.display(), error);
+        T::default()
+    })
+}
+
+
+/// Save a RON file to the data directory, replacing any old one atomically so it's never left half-written
+pub fn save_data_file<T: Serialize>(file_name: &str, data: &T) {
+    let path = match data_file_path(file_name) {
+        Some(path) => path,
+        None => return,
+    };
+
+    let contents = match ron::ser::to_string_pretty(data, default()) {
+        Ok(contents) => contents,
+        Err(error) => {
+            warn!("Couldn't serialize {}: {}", file_name, error);
+            return;
+        },
+    };
+    let temp_path = path.with_extension("ron.tmp");
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&temp_path, contents))
+        .and_then(|_| fs::rename(&temp_path, &path));
+    if let Err(error) = result {
+        warn!("Couldn't save {}: {}", path.display(), error);
+    }
+}
+
+
+/// Add a row to the end of a CSV file, starting the file with the header if it's new
+pub fn append_csv_row(path: &Path, header: &str, row: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let new = !path.exists();
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    if new {
+        writeln!(file, "{}", header)?;
+    }
+    writeln!(file, "{}", row)
+}
+
+
+/// Add a row to a CSV file in the data directory, warning rather than failing if it can't be written
+pub fn append_data_csv_row(file_name: &str, header: &str, row: &str) {
+    let path = match data_file_path(file_name) {
+        Some(path) => path,
+        None => return,
+    };
+    if let Err(error) = append_csv_row(&path, header, row) {
+        warn!("Couldn't write to {}: {}", path.display(), error);
+    }
+}
+
+
+/// Today's date (UTC) as YYYY-MM-DD
+pub fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs() / 86400);
+    date_from_days(days as i64)
+}
+
+
+/// Date (as YYYY-MM-DD) the given number of days after 1970-01-01
+fn date_from_days(days: i64) -> String {
+    // Counting in 400 year eras from 0000-03-01, so leap days fall at the end of each year
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Command line arguments, after the program name
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        std::iter::once("bevy-pong".to_string()).chain(args)
+    }
+
+    #[test]
+    fn arena_size_comes_from_the_arguments() {
+        let arena = Arena::from_args(args(&["--width", "1000", "--height=700"]));
+        assert_eq!((arena.width, arena.height), (1000., 700.));
+    }
+
+    #[test]
+    fn invalid_arena_sizes_are_ignored() {
+        let arena = Arena::from_args(args(&["--width", "tiny", "--height=10"]));
+        assert_eq!((arena.width, arena.height), (DEFAULT_ARENA_WIDTH, DEFAULT_ARENA_HEIGHT));
+    }
+
+    #[test]
+    fn seed_comes_from_the_arguments() {
+        assert_eq!(GameRng::seed_from_args(args(&["--seed", "42"])), 42);
+        assert_eq!(GameRng::seed_from_args(args(&["--seed=-1"])), DEFAULT_SEED);
+        assert_eq!(GameRng::seed_from_args(args(&[])), DEFAULT_SEED);
+    }
+
+    #[test]
+    fn music_ducks_on_a_goal_and_recovers_afterward() {
+        let audio_settings = AudioSettings { master_volume: 0.5, muted: false, music_enabled: true };
+        let mut music_duck = MusicDuck(Timer::from_seconds(MUSIC_DUCK_DURATION, false));
+        let full_volume = MUSIC_VOLUME * 0.5;
+        assert!(music_duck.music_volume(&audio_settings) < full_volume);
+
+        music_duck.0.tick(Duration::from_secs_f32(MUSIC_DUCK_DURATION * 0.5));
+        let halfway = music_duck.music_volume(&audio_settings);
+        assert!(halfway > full_volume * (1. - MUSIC_DUCK_AMOUNT) && halfway < full_volume);
+
+        music_duck.0.tick(Duration::from_secs_f32(MUSIC_DUCK_DURATION));
+        assert_eq!(music_duck.music_volume(&audio_settings), full_volume);
+        assert_eq!(music_duck.music_volume(&AudioSettings { muted: true, ..audio_settings }), 0.);
+        assert_eq!(music_duck.music_volume(&AudioSettings { music_enabled: false, ..audio_settings }), 0.);
+    }
+
+    #[test]
+    fn music_stems_build_as_the_score_nears_the_winning_score() {
+        let audio_settings = AudioSettings { master_volume: 1.0, muted: false, music_enabled: true };
+        let stems = MusicStems { enabled: true, sinks: Vec::new() };
+        let winning_score = WinningScore(11);
+        let total_volume = |player, opponent| {
+            let intensity = MusicStems::intensity(&Scoreboard { player, opponent }, &winning_score);
+            (0..MUSIC_STEMS.len()).map(|index| stems.stem_volume(index, intensity, &audio_settings)).sum::<f32>()
+        };
+        assert_eq!(total_volume(0, 0), 0.);
+        let mut previous = 0.;
+        for score in 1..=10 {
+            let volume = total_volume(score, 0);
+            assert!(volume > previous);
+            previous = volume;
+        }
+        assert_eq!(total_volume(3, 10), MUSIC_STEM_VOLUME * MUSIC_STEMS.len() as f32);
+
+        let full_intensity = MusicStems::intensity(&Scoreboard { player: 10, opponent: 0 }, &winning_score);
+        for audio_settings in [
+            AudioSettings { muted: true, ..audio_settings },
+            AudioSettings { music_enabled: false, ..audio_settings },
+        ] {
+            assert_eq!(stems.stem_volume(0, full_intensity, &audio_settings), 0.);
+        }
+        let disabled = MusicStems { enabled: false, sinks: Vec::new() };
+        assert_eq!(disabled.stem_volume(0, full_intensity, &audio_settings), 0.);
+    }
+
+    #[test]
+    fn replay_buffer_holds_the_configured_seconds_dropping_the_oldest_ticks() {
+        let mut buffer = ReplayBuffer::new(4.);
+        assert_eq!(buffer.capacity(), 240);
+
+        for tick in 0..250 {
+            buffer.push(ReplayFrame { player_y: tick as f32, ..default() });
+        }
+        assert_eq!(buffer.frames.len(), 240);
+        assert_eq!(buffer.frames.front().unwrap().player_y, 10.);
+        assert_eq!(buffer.frames.back().unwrap().player_y, 249.);
+
+        // Kept within the range it can be set to
+        assert_eq!(ReplayBuffer::new(60.).replay_seconds(), MAX_REPLAY_SECONDS);
+
+        // Shortening it keeps the latest ticks
+        buffer.set_replay_seconds(3.);
+        assert_eq!(buffer.frames.len(), 180);
+        assert_eq!(buffer.frames.front().unwrap().player_y, 70.);
+        assert_eq!(buffer.frames.back().unwrap().player_y, 249.);
+    }
+
+    #[test]
+    fn input_sources_cycle_through_each_device_and_the_ai_then_back_to_automatic() {
+        let gamepads = [Gamepad(0), Gamepad(2)];
+        let mut source = None;
+        let mut names = Vec::new();
+        for _ in 0..7 {
+            source = InputSource::cycle(source, &gamepads);
+            names.push(InputSource::name(source));
+        }
+        assert_eq!(names, ["Mouse", "Keyboard", "Gamepad 1", "Gamepad 3", "AI", "Auto", "Mouse"]);
+
+        // A gamepad that's since been disconnected
+        assert_eq!(InputSource::cycle(Some(InputSource::Gamepad(Gamepad(1))), &gamepads), None);
+    }
+
+    #[test]
+    fn replay_plays_back_each_tick_in_turn_from_the_oldest() {
+        let mut buffer = ReplayBuffer::new(3.);
+        for tick in 0..200 {
+            buffer.push(ReplayFrame { player_y: tick as f32, ..default() });
+        }
+
+        assert_eq!(buffer.frame_at(0.).unwrap().player_y, 20.);
+        assert_eq!(buffer.frame_at(TIME_STEP * 10.5).unwrap().player_y, 30.);
+        assert_eq!(buffer.frame_at(3. - TIME_STEP * 0.5).unwrap().player_y, 199.);
+        assert!(buffer.frame_at(3. + TIME_STEP * 0.5).is_none());
+    }
+
+    #[test]
+    fn serve_charge_starts_once_per_countdown_and_not_after_an_early_serve() {
+        let mut serve_charge = ServeCharge { enabled: true, started: false, sink: None };
+        let mut countdown = Timer::from_seconds(SERVE_COUNTDOWN, false);
+        assert!(!serve_charge.should_start(&countdown));
+
+        // Into its final stretch, only the once
+        countdown.tick(Duration::from_secs_f32(SERVE_COUNTDOWN - SERVE_CHARGE_DURATION * 0.5));
+        assert!(serve_charge.should_start(&countdown));
+        assert!(!serve_charge.should_start(&countdown));
+
+        // Served early, before it got there
+        countdown.reset();
+        assert!(!serve_charge.should_start(&countdown));
+        countdown.tick(countdown.duration());
+        assert!(!serve_charge.should_start(&countdown));
+
+        countdown.reset();
+        serve_charge.enabled = false;
+        countdown.tick(Duration::from_secs_f32(SERVE_COUNTDOWN - SERVE_CHARGE_DURATION * 0.5));
+        assert!(!serve_charge.should_start(&countdown));
+    }
+
+    #[test]
+    fn match_stats_row_lists_each_column() {
+        let match_stats = MatchStats { longest_rally: 14, duration: 95.4, ..default() };
+        let score = Scoreboard { player: 11, opponent: 7 };
+        assert_eq!(
+            match_stats.csv_row("2026-10-15", "Single Player", Difficulty::Hard, &score),
+            "2026-10-15,Single Player,Hard,11,7,95,14",
+        );
+    }
+
+    #[test]
+    fn csv_rows_are_appended_after_a_single_header() {
+        let path = std::env::temp_dir().join(format!("bevy-pong-test-{}", std::process::id())).join("stats.csv");
+        let _ = fs::remove_file(&path);
+
+        append_csv_row(&path, "a,b", "1,2").unwrap();
+        append_csv_row(&path, "a,b", "3,4").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a,b\n1,2\n3,4\n");
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn days_since_the_epoch_convert_to_dates() {
+        assert_eq!(date_from_days(0), "1970-01-01");
+        assert_eq!(date_from_days(11_016), "2000-02-29");
+        assert_eq!(date_from_days(20_741), "2026-10-15");
+    }
+
+    #[test]
+    fn rally_leaderboard_keeps_the_longest_rallies_in_order() {
+        let mut leaderboard = RallyLeaderboard::default();
+        for count in [5, 12, 8] {
+            assert!(leaderboard.record(count, "2026-10-14"));
+        }
+        assert!(!leaderboard.record(0, "2026-10-14"));
+        let counts = |leaderboard: &RallyLeaderboard| {
+            leaderboard.rallies.iter().map(|rally| rally.count).collect::<Vec<_>>()
+        };
+        assert_eq!(counts(&leaderboard), [12, 8, 5]);
+
+        // Filled up, then only a rally longer than the shortest one gets on, pushing that one off
+        for count in 20..27 {
+            leaderboard.record(count, "2026-10-14");
+        }
+        assert_eq!(leaderboard.rallies.len(), RALLY_LEADERBOARD_SIZE);
+        assert!(!leaderboard.record(5, "2026-10-15"));
+        assert!(leaderboard.record(10, "2026-10-15"));
+        assert_eq!(counts(&leaderboard), [26, 25, 24, 23, 22, 21, 20, 12, 10, 8]);
+        assert_eq!(leaderboard.rallies[8], RallyRecord { count: 10, date: "2026-10-15".to_string() });
+
+        // A tie goes below the rally it ties with
+        assert!(leaderboard.record(12, "2026-10-15"));
+        assert_eq!(leaderboard.rallies[7].date, "2026-10-14");
+        assert_eq!(leaderboard.rallies[8].date, "2026-10-15");
+    }
+
+    #[test]
+    fn ui_scale_cycles_through_the_presets() {
+        let mut ui_scale = UiScale(1.0);
+        let mut scales = vec![];
+        for _ in 0..UI_SCALE_PRESETS.len() {
+            ui_scale = ui_scale.next();
+            scales.push(ui_scale.0);
+        }
+        assert_eq!(scales, [1.25, 1.5, 0.75, 1.0]);
+
+        // A scale between the presets moves up to the next one
+        assert_eq!(UiScale(1.1).next().0, 1.25);
+    }
+}
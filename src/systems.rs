@@ -0,0 +1,5959 @@
+use bevy::app::AppExit;
+use bevy::audio::AudioSink;
+use bevy::diagnostic::{Diagnostic, Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::ecs::schedule::ShouldRun;
+use bevy::ecs::system::SystemParam;
+use bevy::input::mouse::MouseMotion;
+use bevy::prelude::*;
+use bevy::window::{WindowFocused, WindowMode, WindowResized};
+use bevy::sprite::collide_aabb::{collide, Collision};
+use rand::Rng;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::time::Duration;
+use crate::components::*;
+use crate::constants::*;
+use crate::resources::*;
+
+
+// Rules and modes deciding how balls bounce and score
+#[derive(SystemParam)]
+pub struct MatchRules<'w, 's> {
+    pub serve_rule: Res<'w, ServeRule>,
+    pub bounce_mode: Res<'w, BounceMode>,
+    pub game_speed: Res<'w, GameSpeed>,
+    pub sticky_paddle: Res<'w, StickyPaddle>,
+    pub multi_ball: Res<'w, MultiBall>,
+    pub practice_mode: Res<'w, PracticeMode>,
+    pub comeback_gap: Res<'w, ComebackGap>,
+    pub config: Res<'w, GameConfig>,
+    #[system_param(ignore)]
+    pub marker: PhantomData<&'s ()>,
+}
+
+
+// How the ball is served: when, from where, and at what angle
+#[derive(SystemParam)]
+pub struct ServeStyle<'w, 's> {
+    pub serve_on_demand: Res<'w, ServeOnDemand>,
+    pub random_serve: Res<'w, RandomServe>,
+    pub serve_spawn: ResMut<'w, ServeSpawn>,
+    pub endless: Res<'w, Endless>,
+    #[system_param(ignore)]
+    pub marker: PhantomData<&'s ()>,
+}
+
+
+// Audio settings, along with the music they control
+#[derive(SystemParam)]
+pub struct AudioControls<'w, 's> {
+    pub audio_settings: ResMut<'w, AudioSettings>,
+    pub music_sink: Res<'w, MusicSink>,
+    pub audio_sinks: Res<'w, Assets<AudioSink>>,
+    #[system_param(ignore)]
+    pub marker: PhantomData<&'s ()>,
+}
+
+
+// Every source of player input, to tell whether anyone's at the controls
+#[derive(SystemParam)]
+pub struct AnyInput<'w, 's> {
+    pub keyboard_input: Res<'w, Input<KeyCode>>,
+    pub mouse_buttons: Res<'w, Input<MouseButton>>,
+    pub gamepad_buttons: Res<'w, Input<GamepadButton>>,
+    pub mouse_motion: EventReader<'w, 's, MouseMotion>,
+}
+
+impl<'w, 's> AnyInput<'w, 's> {
+    /// Whether a key or button was pressed, or the mouse moved, this frame
+    pub fn received(&mut self) -> bool {
+        let mouse_moved = self.mouse_motion.iter().count() > 0;
+        mouse_moved
+            || self.keyboard_input.get_just_pressed().next().is_some()
+            || self.mouse_buttons.get_just_pressed().next().is_some()
+            || self.gamepad_buttons.get_just_pressed().next().is_some()
+    }
+}
+
+
+// Match state the attract mode demo plays with, put aside while it runs
+#[derive(SystemParam)]
+pub struct DemoMatchState<'w, 's> {
+    pub scoreboard: ResMut<'w, Scoreboard>,
+    pub rally_count: ResMut<'w, RallyCount>,
+    pub stats: ResMut<'w, MatchStats>,
+    pub player_turn: ResMut<'w, PlayerTurn>,
+    pub ball_spawn_timer: ResMut<'w, BallSpawnTimer>,
+    pub timed_match: ResMut<'w, TimedMatch>,
+    pub onboarding: ResMut<'w, Onboarding>,
+    pub heatmap: ResMut<'w, Heatmap>,
+    pub rng: ResMut<'w, GameRng>,
+    pub two_player: ResMut<'w, TwoPlayer>,
+    pub serve_on_demand: ResMut<'w, ServeOnDemand>,
+    pub endless: ResMut<'w, Endless>,
+    pub serve_spawn: ResMut<'w, ServeSpawn>,
+    #[system_param(ignore)]
+    pub marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> DemoMatchState<'w, 's> {
+    fn snapshot(&self) -> DemoSnapshot {
+        DemoSnapshot {
+            scoreboard: self.scoreboard.clone(),
+            rally_count: self.rally_count.0,
+            stats: self.stats.clone(),
+            player_turn: self.player_turn.0,
+            ball_spawn_timer: self.ball_spawn_timer.0.clone(),
+            match_clock: self.timed_match.remaining.clone(),
+            onboarding: self.onboarding.clone(),
+            heatmap: self.heatmap.clone(),
+            rng: self.rng.clone(),
+            two_player: self.two_player.0,
+            serve_on_demand: self.serve_on_demand.0,
+            endless: self.endless.clone(),
+            serve_spawn: *self.serve_spawn,
+        }
+    }
+
+    fn restore(&mut self, saved: DemoSnapshot) {
+        *self.scoreboard = saved.scoreboard;
+        self.rally_count.0 = saved.rally_count;
+        *self.stats = saved.stats;
+        self.player_turn.0 = saved.player_turn;
+        self.ball_spawn_timer.0 = saved.ball_spawn_timer;
+        self.timed_match.remaining = saved.match_clock;
+        *self.onboarding = saved.onboarding;
+        *self.heatmap = saved.heatmap;
+        *self.rng = saved.rng;
+        self.two_player.0 = saved.two_player;
+        self.serve_on_demand.0 = saved.serve_on_demand;
+        *self.endless = saved.endless;
+        *self.serve_spawn = saved.serve_spawn;
+    }
+}
+
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    audio_settings: Res<AudioSettings>,
+    mut music_stems: ResMut<MusicStems>,
+) {
+    // Camera
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(MainCamera)
+        .insert(CameraZoom {
+            fit: 1.,
+            punch: finished_timer(CAMERA_PUNCH_DURATION),
+        });
+
+    // Play music and load other sounds
+    let music = audio.play_with_settings(
+        asset_server.load("sounds/Music.wav"),
+        PlaybackSettings::LOOP.with_volume(audio_settings.music_volume(MUSIC_VOLUME)),
+    );
+    commands.insert_resource(MusicSink(audio_sinks.get_handle(music)));
+    if music_stems.enabled {
+        for stem in MUSIC_STEMS {
+            let stem = audio.play_with_settings(asset_server.load(stem), PlaybackSettings::LOOP.with_volume(0.));
+            music_stems.sinks.push(audio_sinks.get_handle(stem));
+        }
+    }
+    let match_point_music = audio.play_with_settings(
+        asset_server.load("sounds/MatchPointMusic.wav"),
+        PlaybackSettings::LOOP.with_volume(0.),
+    );
+    commands.insert_resource(MatchPointMusicSink(audio_sinks.get_handle(match_point_music)));
+    let hit_sound = asset_server.load("sounds/PaddleHitSound.wav");
+    let goal_sound = asset_server.load("sounds/GoalSound.wav");
+    // Walls reuse the paddle hit, played faster (higher pitched) and quieter
+    let wall_sound = asset_server.load("sounds/PaddleHitSound.wav");
+    commands.insert_resource(HitSound(hit_sound));
+    commands.insert_resource(GoalSound(goal_sound));
+    commands.insert_resource(ComebackSound(asset_server.load("sounds/ComebackSound.wav")));
+    commands.insert_resource(WallSound(wall_sound));
+    commands.insert_resource(ServeChargeSound(asset_server.load("sounds/ServeChargeSound.wav")));
+
+    // UI Camera
+    commands.spawn_bundle(UiCameraBundle::default());
+}
+
+
+/// Reseed the game's randomness from the replay when a match starts, so a recorded match and its
+/// playback serve the same way, and start the recording (or playback) over
+pub fn start_replay(
+    replay_mode: Res<ReplayMode>,
+    input_mode: Res<InputMode>,
+    mut replay: ResMut<Replay>,
+    mut rng: ResMut<GameRng>,
+) {
+    match *replay_mode {
+        ReplayMode::Off => return,
+        ReplayMode::Recording => {
+            replay.input_mode = *input_mode;
+            replay.inputs.clear();
+            replay.pauses.clear();
+        },
+        ReplayMode::Playback => {
+            replay.cursor = 0;
+            replay.next_pause = 0;
+        },
+    }
+    *rng = GameRng::new(replay.seed);
+}
+
+
+/// Spawn the net and paddles when a game starts
+pub fn setup_gameplay(
+    mut commands: Commands,
+    travel_fraction: Res<PlayerTravelFraction>,
+    stamina_enabled: Res<StaminaEnabled>,
+    config: Res<GameConfig>,
+    arena: Res<Arena>,
+) {
+    spawn_net(&mut commands, &arena);
+    spawn_travel_zone_marks(&mut commands, &arena, travel_fraction.0);
+
+    // Add player Paddle (left)
+    let player = commands
+        .spawn()
+        .insert(Player)
+        .insert(ColliderKind::Paddle)
+        .insert(Velocity(Vec2::ZERO))
+        .insert(Gameplay)
+        .insert_bundle(SpriteBundle {
+            transform: Transform {
+                translation: Vec3::new(-arena.width * 0.5 + 26., 0., 0.0),
+                ..default()
+            },
+            sprite: Sprite {
+                color: Color::WHITE,
+                custom_size: Some(config.paddle_size),
+                ..default()
+            },
+            ..default()
+        })
+        .id();
+
+    // Add opponent paddle (right)
+    let opponent = commands
+        .spawn()
+        .insert(Opponent)
+        .insert(ColliderKind::Paddle)
+        .insert(Velocity(Vec2::ZERO))
+        .insert(Gameplay)
+        .insert_bundle(SpriteBundle {
+            transform: Transform {
+                translation: Vec3::new(arena.width * 0.5 - 26., 0., 0.0),
+                ..default()
+            },
+            sprite: Sprite {
+                color: Color::WHITE,
+                custom_size: Some(config.paddle_size),
+                ..default()
+            },
+            ..default()
+        })
+        .id();
+
+    if stamina_enabled.0 {
+        add_stamina(&mut commands, &arena, player, -arena.width * 0.5 + 26.);
+        add_stamina(&mut commands, &arena, opponent, arena.width * 0.5 - 26.);
+    }
+}
+
+
+/// Spawn the scoreboard when a game starts
+pub fn spawn_scoreboard(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    ui_scale: Res<UiScale>,
+    arena: Res<Arena>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    // Each score sits to its own side of the net, pushed out toward the edge from it
+    for (is_player, justify_content) in [(true, JustifyContent::FlexEnd), (false, JustifyContent::FlexStart)] {
+        commands
+            .spawn_bundle(top_node(justify_content))
+            .insert(Gameplay)
+            .with_children(|parent| {
+                let mut score = parent.spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: score_margin(&arena, &ui_scale, is_player),
+                        ..default()
+                    },
+                    text: Text::with_section(
+                        "0",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: SCORE_FONT_SIZE * ui_scale.value(),
+                            color: Color::WHITE,
+                        },
+                        default(),
+                    ),
+                    ..default()
+                });
+                score
+                    .insert(ScoreText)
+                    .insert(ScaledText(SCORE_FONT_SIZE))
+                    .insert(ThemedText)
+                    .insert(ScoreAnimation(finished_timer(SCORE_POP_DURATION)));
+                if is_player {
+                    score.insert(PlayerScoreText);
+                } else {
+                    score.insert(OpponentScoreText);
+                }
+            });
+    }
+
+    // Series score, just below the top edge
+    commands
+        .spawn_bundle(top_centered_node())
+        .insert(Gameplay)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect {
+                        top: Val::Percent(2.),
+                        ..default()
+                    },
+                    ..default()
+                },
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: HUD_FONT_SIZE * ui_scale.value(),
+                        color: Color::rgb(0.65, 0.65, 0.65),
+                    },
+                    default(),
+                ),
+                ..default()
+            })
+                .insert(SeriesText)
+                .insert(ScaledText(HUD_FONT_SIZE));
+        });
+
+    // Rally counter, under the scores
+    commands
+        .spawn_bundle(top_centered_node())
+        .insert(Gameplay)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect {
+                        top: Val::Percent(19.),
+                        ..default()
+                    },
+                    ..default()
+                },
+                text: Text::with_section(
+                    "Rally: 0",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: HUD_FONT_SIZE * ui_scale.value(),
+                        color: Color::rgb(0.65, 0.65, 0.65),
+                    },
+                    default(),
+                ),
+                ..default()
+            })
+                .insert(RallyText)
+                .insert(ScaledText(HUD_FONT_SIZE));
+        });
+
+    // Match clock, along the bottom edge
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::FlexStart,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(Gameplay)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect {
+                        bottom: Val::Percent(2.),
+                        ..default()
+                    },
+                    ..default()
+                },
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: MATCH_CLOCK_FONT_SIZE * ui_scale.value(),
+                        color: Color::WHITE,
+                    },
+                    default(),
+                ),
+                ..default()
+            })
+                .insert(MatchClockText)
+                .insert(ScaledText(MATCH_CLOCK_FONT_SIZE))
+                .insert(ThemedText);
+        });
+}
+
+
+/// Full screen node laying out its children centered along the top edge
+fn top_centered_node() -> NodeBundle {
+    top_node(JustifyContent::Center)
+}
+
+
+/// Full-screen UI node laying out its children along the top edge
+fn top_node(justify_content: JustifyContent) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+            position_type: PositionType::Absolute,
+            justify_content,
+            align_items: AlignItems::FlexEnd,  // Coordinates are Y-up so this is at top of screen
+            ..default()
+        },
+        color: Color::NONE.into(),
+        ..default()
+    }
+}
+
+
+/// Spawn the net as a dashed line down the middle, from the top down
+fn spawn_net(commands: &mut Commands, arena: &Arena) {
+    let dash_spacing = NET_DASH_LENGTH + NET_DASH_GAP;
+    let dash_count = (arena.height / dash_spacing).ceil() as usize;
+    for i in 0..dash_count {
+        let y = arena.height * 0.5 - dash_spacing * (i as f32 + 0.5);
+        commands
+            .spawn_bundle(SpriteBundle {
+                transform: Transform {
+                    translation: Vec3::new(0., y, NET_Z),
+                    ..default()
+                },
+                sprite: Sprite {
+                    color: Color::rgb(0.65, 0.65, 0.65),
+                    custom_size: Some(Vec2::new(NET_WIDTH, NET_DASH_LENGTH)),
+                    ..default()
+                },
+                ..default()
+            })
+            .insert(Net)
+            .insert(ArenaFixture)
+            .insert(Gameplay);
+    }
+}
+
+
+/// Mark the edges of the player's allowed travel zone when it's restricted
+fn spawn_travel_zone_marks(commands: &mut Commands, arena: &Arena, travel_fraction: f32) {
+    if travel_fraction >= 1.0 {
+        return;
+    }
+
+    let zone_edge = arena.height * 0.5 * travel_fraction;
+    for y in [-zone_edge, zone_edge] {
+        commands
+            .spawn_bundle(SpriteBundle {
+                transform: Transform {
+                    translation: Vec3::new(-arena.width * 0.5 + 26., y, 0.0),
+                    ..default()
+                },
+                sprite: Sprite {
+                    color: Color::rgb(0.35, 0.35, 0.35),
+                    custom_size: Some(Vec2::new(24., 2.)),
+                    ..default()
+                },
+                ..default()
+            })
+            .insert(ArenaFixture)
+            .insert(Gameplay);
+    }
+}
+
+
+/// Give a paddle stamina, with a stamina bar along the bottom of the screen in front of it
+fn add_stamina(commands: &mut Commands, arena: &Arena, paddle: Entity, paddle_x: f32) {
+    commands.entity(paddle).insert(Stamina { current: STAMINA_MAX, max: STAMINA_MAX });
+
+    commands
+        .spawn()
+        .insert(StaminaBar(paddle))
+        .insert(Gameplay)
+        .insert_bundle(SpriteBundle {
+            transform: Transform {
+                translation: Vec3::new(paddle_x * 0.8, -arena.height * 0.5 + 12., 0.0),
+                ..default()
+            },
+            sprite: Sprite {
+                color: Color::rgb(0.3, 0.8, 0.4),
+                custom_size: Some(STAMINA_BAR_SIZE),
+                ..default()
+            },
+            ..default()
+        });
+}
+
+
+/// Grab and hide cursor
+pub fn grab_cursor(mut windows: ResMut<Windows>) {
+    let window = windows.get_primary_mut().unwrap();
+    window.set_cursor_lock_mode(true);
+    window.set_cursor_visibility(false);
+}
+
+
+/// Release and show cursor
+pub fn release_cursor(mut windows: ResMut<Windows>) {
+    let window = windows.get_primary_mut().unwrap();
+    window.set_cursor_lock_mode(false);
+    window.set_cursor_visibility(true);
+}
+
+
+/// Show the title screen
+pub fn spawn_menu(
+    asset_server: Res<AssetServer>,
+    ui_scale: Res<UiScale>,
+    mut focus: ResMut<MenuFocus>,
+    mut commands: Commands,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    focus.0 = 0;
+
+    commands
+        .spawn_bundle(menu_screen_node())
+        .insert(MenuUi)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect {
+                        bottom: Val::Px(32.),
+                        ..default()
+                    },
+                    ..default()
+                },
+                text: Text::with_section(
+                    "Bevy Pong",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: TITLE_FONT_SIZE * ui_scale.value(),
+                        color: Color::WHITE,
+                    },
+                    default(),
+                ),
+                ..default()
+            })
+                .insert(ScaledText(TITLE_FONT_SIZE))
+                .insert(ThemedText);
+            for (index, (label, action)) in [
+                ("Play", MenuAction::Play),
+                ("Endless", MenuAction::PlayEndless),
+                ("Settings", MenuAction::OpenSettings),
+                ("Quit", MenuAction::Quit),
+            ].into_iter().enumerate() {
+                spawn_menu_button(parent, font.clone(), &ui_scale, label.to_string(), MenuButton { action, index });
+            }
+        });
+}
+
+
+/// Show the settings screen, with a button to change each setting
+pub fn spawn_settings_menu(
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    ui_scale: Res<UiScale>,
+    mut focus: ResMut<MenuFocus>,
+    mut commands: Commands,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    focus.0 = 0;
+
+    commands
+        .spawn_bundle(menu_screen_node())
+        .insert(SettingsUi)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect {
+                        bottom: Val::Px(32.),
+                        ..default()
+                    },
+                    ..default()
+                },
+                text: Text::with_section(
+                    "Settings",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: HEADING_FONT_SIZE * ui_scale.value(),
+                        color: Color::WHITE,
+                    },
+                    default(),
+                ),
+                ..default()
+            })
+                .insert(ScaledText(HEADING_FONT_SIZE))
+                .insert(ThemedText);
+            // Too many to fit one column, so laid out in two, down the left one then the right,
+            // with the way out beneath them
+            let columns: [&[MenuAction]; 2] = [
+                &[
+                    MenuAction::CycleDifficulty,
+                    MenuAction::ToggleInputMode,
+                    MenuAction::CyclePlayerInput,
+                    MenuAction::CycleOpponentInput,
+                    MenuAction::ToggleCornerServes,
+                    MenuAction::CycleReplaySeconds,
+                ],
+                &[
+                    MenuAction::ToggleMute,
+                    MenuAction::ToggleMusic,
+                    MenuAction::CycleTheme,
+                    MenuAction::ToggleDistinctPaddles,
+                    MenuAction::ToggleReduceMotion,
+                    MenuAction::CycleUiScale,
+                ],
+            ];
+            let mut index = 0;
+            let mut spawn_buttons = |parent: &mut ChildBuilder, actions: &[MenuAction]| {
+                for &action in actions {
+                    let label = settings_label(action, &settings);
+                    spawn_menu_button(parent, font.clone(), &ui_scale, label, MenuButton { action, index });
+                    index += 1;
+                }
+            };
+            parent.spawn_bundle(menu_group_node(FlexDirection::Row)).with_children(|parent| {
+                for column in columns {
+                    parent
+                        .spawn_bundle(menu_group_node(FlexDirection::ColumnReverse))
+                        .with_children(|parent| spawn_buttons(parent, column));
+                }
+            });
+            parent
+                .spawn_bundle(menu_group_node(FlexDirection::Row))
+                .with_children(|parent| spawn_buttons(parent, &[MenuAction::OpenKeyBindings, MenuAction::Back]));
+        });
+}
+
+
+/// Node laying out a group of menu buttons in a row or column
+fn menu_group_node(flex_direction: FlexDirection) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            flex_direction,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        color: Color::NONE.into(),
+        ..default()
+    }
+}
+
+
+/// Full screen node laying out a menu screen's title and buttons in a centered column
+fn menu_screen_node() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+            position_type: PositionType::Absolute,
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        color: Color::NONE.into(),
+        ..default()
+    }
+}
+
+
+/// Spawn a menu button with a text label
+fn spawn_menu_button(parent: &mut ChildBuilder, font: Handle<Font>, ui_scale: &UiScale, label: String, button: MenuButton) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: menu_button_style(ui_scale),
+            color: MENU_BUTTON_COLOR.into(),
+            ..default()
+        })
+        .insert(button)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    label,
+                    TextStyle {
+                        font,
+                        font_size: MENU_BUTTON_FONT_SIZE * ui_scale.value(),
+                        color: Color::WHITE,
+                    },
+                    default(),
+                ),
+                ..default()
+            })
+                .insert(ScaledText(MENU_BUTTON_FONT_SIZE))
+                .insert(ThemedText);
+        });
+}
+
+
+/// Layout of a menu button, sized and spaced out to the UI scale
+fn menu_button_style(ui_scale: &UiScale) -> Style {
+    let size = MENU_BUTTON_SIZE * ui_scale.value();
+    Style {
+        size: Size::new(Val::Px(size.x), Val::Px(size.y)),
+        margin: Rect::all(Val::Px(MENU_BUTTON_MARGIN * ui_scale.value())),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    }
+}
+
+
+/// Label of a settings screen button, showing the current value of its setting
+fn settings_label(action: MenuAction, settings: &Settings) -> String {
+    let on_off = |on: bool| if on { "On" } else { "Off" };
+    match action {
+        MenuAction::CycleDifficulty => format!("Difficulty: {}", settings.difficulty.name()),
+        MenuAction::ToggleInputMode => format!("Controls: {}", match settings.input_mode {
+            InputMode::Mouse => "Mouse",
+            InputMode::Keyboard => "Keyboard",
+        }),
+        MenuAction::CyclePlayerInput => format!("Left Paddle: {}", InputSource::name(settings.player_input)),
+        MenuAction::CycleOpponentInput => format!("Right Paddle: {}", InputSource::name(settings.opponent_input)),
+        MenuAction::ToggleMute => format!("Sound: {}", on_off(!settings.muted)),
+        MenuAction::ToggleMusic => format!("Music: {}", on_off(settings.music_enabled)),
+        MenuAction::CycleTheme => format!("Theme: {}", settings.theme.name()),
+        MenuAction::ToggleDistinctPaddles => {
+            format!("Paddle Colors: {}", if settings.distinct_paddles { "Distinct" } else { "Theme" })
+        }
+        MenuAction::ToggleReduceMotion => format!("Reduce Motion: {}", on_off(settings.reduce_motion)),
+        MenuAction::CycleUiScale => format!("UI Scale: {}%", (settings.ui_scale * 100.).round()),
+        MenuAction::CycleReplaySeconds => format!("Replay Length: {}s", settings.replay_seconds),
+        MenuAction::ToggleCornerServes => {
+            format!("Serve From: {}", if settings.corner_serves { "Corners" } else { "Center" })
+        }
+        MenuAction::OpenKeyBindings => "Key Bindings".to_string(),
+        MenuAction::Back => "Back".to_string(),
+        _ => String::new(),
+    }
+}
+
+
+/// Show the key bindings screen, with a button to rebind each action
+pub fn spawn_key_bindings_menu(
+    asset_server: Res<AssetServer>,
+    key_bindings: Res<KeyBindings>,
+    rebinding: Res<Rebinding>,
+    ui_scale: Res<UiScale>,
+    mut focus: ResMut<MenuFocus>,
+    mut commands: Commands,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    focus.0 = 0;
+
+    commands
+        .spawn_bundle(menu_screen_node())
+        .insert(KeyBindingsUi)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect {
+                        bottom: Val::Px(32.),
+                        ..default()
+                    },
+                    ..default()
+                },
+                text: Text::with_section(
+                    "Key Bindings",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: HEADING_FONT_SIZE * ui_scale.value(),
+                        color: Color::WHITE,
+                    },
+                    default(),
+                ),
+                ..default()
+            })
+                .insert(ScaledText(HEADING_FONT_SIZE))
+                .insert(ThemedText);
+            let actions = KeyAction::ALL.into_iter().map(MenuAction::Rebind).chain([MenuAction::Back]);
+            for (index, action) in actions.enumerate() {
+                let label = key_binding_label(action, &key_bindings, &rebinding);
+                spawn_menu_button(parent, font.clone(), &ui_scale, label, MenuButton { action, index });
+            }
+        });
+}
+
+
+/// Label of a key bindings screen button, showing the key bound to its action (or a prompt for
+/// one, while rebinding it)
+fn key_binding_label(action: MenuAction, key_bindings: &KeyBindings, rebinding: &Rebinding) -> String {
+    match action {
+        MenuAction::Rebind(key_action) if rebinding.0 == Some(key_action) => {
+            format!("{}: press a key", key_action.name())
+        }
+        MenuAction::Rebind(key_action) => format!("{}: {}", key_action.name(), key_name(key_bindings.key(key_action))),
+        MenuAction::Back => "Back".to_string(),
+        _ => String::new(),
+    }
+}
+
+
+/// Bind the next key pressed to the action waiting for one, or cancel with Escape
+///
+/// Keys already bound to another action are ignored, so no key ever does two things.
+pub fn capture_rebind(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut key_bindings: ResMut<KeyBindings>,
+) {
+    let action = match rebinding.0 {
+        Some(action) => action,
+        None => return,
+    };
+
+    for &key in keyboard_input.get_just_pressed() {
+        if key == KeyCode::Escape || key_bindings.bind(action, key) {
+            rebinding.0 = None;
+            return;
+        }
+    }
+}
+
+
+/// Refresh the key bindings screen's labels when a binding changes, or starts waiting for a key
+pub fn update_key_binding_labels(
+    button_query: Query<(&MenuButton, &Children)>,
+    mut text_query: Query<&mut Text>,
+    key_bindings: Res<KeyBindings>,
+    rebinding: Res<Rebinding>,
+) {
+    if !key_bindings.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+
+    for (button, children) in button_query.iter() {
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                text.sections[0].value = key_binding_label(button.action, &key_bindings, &rebinding);
+            }
+        }
+    }
+}
+
+
+/// Move the menu focus with Up/Down (or the mouse), activating the focused button with Enter or
+/// Space (or a click), and going back with Escape
+pub fn navigate_menu(
+    keyboard_input: Res<Input<KeyCode>>,
+    interaction_query: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    button_query: Query<&MenuButton>,
+    rebinding: Res<Rebinding>,
+    app_state: Res<State<AppState>>,
+    mut focus: ResMut<MenuFocus>,
+    mut menu_actions: EventWriter<MenuAction>,
+) {
+    let button_count = button_query.iter().count();
+    // Keys pressed while rebinding (including the one that finishes it) are for the binding, and
+    // input during the demo only ends it
+    if button_count == 0
+        || rebinding.0.is_some()
+        || rebinding.is_changed()
+        || *app_state.current() == AppState::Demo
+    {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        focus.0 = (focus.0 + button_count - 1) % button_count;
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        focus.0 = (focus.0 + 1) % button_count;
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Return, KeyCode::Space]) {
+        if let Some(button) = button_query.iter().find(|button| button.index == focus.0) {
+            menu_actions.send(button.action);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        menu_actions.send(MenuAction::Back);
+    }
+
+    for (interaction, button) in interaction_query.iter() {
+        match interaction {
+            Interaction::Clicked => {
+                focus.0 = button.index;
+                menu_actions.send(button.action);
+            }
+            Interaction::Hovered => focus.0 = button.index,
+            Interaction::None => {}
+        }
+    }
+}
+
+
+/// Carry out activated menu actions
+#[allow(clippy::too_many_arguments)]
+pub fn apply_menu_actions(
+    mut menu_actions: EventReader<MenuAction>,
+    mut app_state: ResMut<State<AppState>>,
+    mut app_exit: EventWriter<AppExit>,
+    mut difficulty: ResMut<Difficulty>,
+    mut input_mode: ResMut<InputMode>,
+    mut audio: AudioControls,
+    mut theme: ResMut<Theme>,
+    mut reduce_motion: ResMut<ReduceMotion>,
+    mut ui_scale: ResMut<UiScale>,
+    mut rebinding: ResMut<Rebinding>,
+    mut endless: ResMut<Endless>,
+    mut replay_buffer: ResMut<ReplayBuffer>,
+    mut serve_spawn: ResMut<ServeSpawn>,
+    mut input_assignments: ResMut<InputAssignments>,
+    gamepads: Res<Gamepads>,
+) {
+    // Gamepads that can be assigned to a paddle, in a steady order
+    let mut connected_gamepads: Vec<Gamepad> = gamepads.iter().copied().collect();
+    connected_gamepads.sort_by_key(|gamepad| gamepad.0);
+
+    for action in menu_actions.iter() {
+        // Ignore state changes if another transition is already queued
+        let _ = match action {
+            MenuAction::Play | MenuAction::PlayEndless => app_state.set(AppState::Playing),
+            MenuAction::OpenSettings => app_state.set(AppState::Settings),
+            MenuAction::OpenKeyBindings => app_state.set(AppState::KeyBindings),
+            MenuAction::Back if *app_state.current() == AppState::Settings => app_state.set(AppState::Menu),
+            MenuAction::Back if *app_state.current() == AppState::KeyBindings => app_state.set(AppState::Settings),
+            _ => Ok(()),
+        };
+
+        match action {
+            MenuAction::Play => endless.enabled = false,
+            MenuAction::PlayEndless => endless.enabled = true,
+            MenuAction::Quit => app_exit.send(AppExit),
+            MenuAction::CycleDifficulty => {
+                *difficulty = match *difficulty {
+                    Difficulty::Easy => Difficulty::Medium,
+                    Difficulty::Medium => Difficulty::Hard,
+                    Difficulty::Hard => Difficulty::Easy,
+                };
+            }
+            MenuAction::ToggleInputMode => {
+                *input_mode = match *input_mode {
+                    InputMode::Mouse => InputMode::Keyboard,
+                    InputMode::Keyboard => InputMode::Mouse,
+                };
+            }
+            MenuAction::CyclePlayerInput => {
+                input_assignments.player = InputSource::cycle(input_assignments.player, &connected_gamepads);
+            }
+            MenuAction::CycleOpponentInput => {
+                input_assignments.opponent = InputSource::cycle(input_assignments.opponent, &connected_gamepads);
+            }
+            MenuAction::ToggleMute => {
+                audio.audio_settings.muted = !audio.audio_settings.muted;
+                apply_mute(&audio.audio_settings, &audio.music_sink, &audio.audio_sinks);
+            }
+            MenuAction::ToggleMusic => audio.audio_settings.music_enabled = !audio.audio_settings.music_enabled,
+            MenuAction::CycleTheme => *theme = theme.with_preset(theme.preset.next()),
+            MenuAction::ToggleDistinctPaddles => theme.distinct_paddles = !theme.distinct_paddles,
+            MenuAction::ToggleReduceMotion => reduce_motion.0 = !reduce_motion.0,
+            MenuAction::CycleUiScale => *ui_scale = ui_scale.next(),
+            MenuAction::CycleReplaySeconds => {
+                let replay_seconds = replay_buffer.replay_seconds();
+                let next = if replay_seconds >= MAX_REPLAY_SECONDS { MIN_REPLAY_SECONDS } else { replay_seconds.floor() + 1. };
+                replay_buffer.set_replay_seconds(next);
+            }
+            MenuAction::ToggleCornerServes => {
+                *serve_spawn = match *serve_spawn {
+                    ServeSpawn::Center => ServeSpawn::Corners { top: true },
+                    ServeSpawn::Corners { .. } => ServeSpawn::Center,
+                };
+            }
+            MenuAction::Rebind(key_action) => rebinding.0 = Some(*key_action),
+            _ => {}
+        }
+    }
+}
+
+
+/// Color menu buttons by whether they're pressed or have focus
+pub fn color_menu_buttons(
+    mut button_query: Query<(&Interaction, &MenuButton, &mut UiColor)>,
+    focus: Res<MenuFocus>,
+) {
+    for (interaction, button, mut color) in button_query.iter_mut() {
+        *color = if *interaction == Interaction::Clicked {
+            MENU_BUTTON_PRESSED_COLOR
+        } else if button.index == focus.0 {
+            MENU_BUTTON_FOCUSED_COLOR
+        } else {
+            MENU_BUTTON_COLOR
+        }.into();
+    }
+}
+
+
+/// Keep the settings screen button labels in line with the settings, however they were changed
+pub fn update_settings_labels(
+    button_query: Query<(&MenuButton, &Children)>,
+    mut text_query: Query<&mut Text>,
+    settings: Res<Settings>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for (button, children) in button_query.iter() {
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                text.sections[0].value = settings_label(button.action, &settings);
+            }
+        }
+    }
+}
+
+
+/// Start the attract mode demo behind the title menu once it's been left idle for a while
+pub fn start_demo_when_idle(
+    time: Res<Time>,
+    mut input: AnyInput,
+    mut attract_mode: ResMut<AttractMode>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if input.received() {
+        attract_mode.idle.reset();
+        return;
+    }
+    if attract_mode.idle.tick(time.delta()).just_finished() {
+        attract_mode.idle.reset();
+        let _ = app_state.push(AppState::Demo);
+    }
+}
+
+
+/// Return to the interactive menu on any input during the demo
+pub fn end_demo_on_input(mut input: AnyInput, mut app_state: ResMut<State<AppState>>) {
+    if input.received() {
+        let _ = app_state.pop();
+    }
+}
+
+
+/// Put the match state aside, and set up an AI-vs-AI match for the demo
+pub fn start_demo(mut match_state: DemoMatchState, mut attract_mode: ResMut<AttractMode>) {
+    attract_mode.saved = Some(match_state.snapshot());
+
+    match_state.ball_spawn_timer.0.reset();
+    match_state.two_player.0 = false;
+    match_state.serve_on_demand.0 = false;
+    match_state.endless.enabled = false;
+    *match_state.serve_spawn = ServeSpawn::Center;
+}
+
+
+/// Restore the match state the demo played with to how it was before
+pub fn end_demo(mut match_state: DemoMatchState, mut attract_mode: ResMut<AttractMode>) {
+    if let Some(saved) = attract_mode.saved.take() {
+        match_state.restore(saved);
+    }
+}
+
+
+/// Spawn the magnet obstacle, if magnet mode is enabled
+pub fn setup_magnet(magnet_mode: Res<MagnetMode>, arena: Res<Arena>, mut commands: Commands) {
+    if magnet_mode.0 {
+        spawn_magnet(&mut commands, &arena);
+    }
+}
+
+
+/// Spawn the magnet obstacle above the middle of the arena
+fn spawn_magnet(commands: &mut Commands, arena: &Arena) {
+    commands
+        .spawn()
+        .insert(Magnet { strength: 600., radius: 150. })
+        .insert(ArenaFixture)
+        .insert(Gameplay)
+        .insert_bundle(SpriteBundle {
+            transform: Transform {
+                translation: Vec3::new(0., arena.height * 0.25, 0.0),
+                ..default()
+            },
+            sprite: Sprite {
+                color: Color::rgb(0.6, 0.3, 0.9),
+                custom_size: Some(Vec2::new(14., 14.)),
+                ..default()
+            },
+            ..default()
+        });
+}
+
+
+/// Spawn solid net segments above and below the central gap, if the solid net is enabled
+pub fn setup_solid_net(solid_net: Res<SolidNet>, arena: Res<Arena>, mut commands: Commands) {
+    if solid_net.enabled {
+        spawn_solid_net(&mut commands, &arena, solid_net.gap);
+    }
+}
+
+
+/// Spawn solid net segments above and below a central gap
+fn spawn_solid_net(commands: &mut Commands, arena: &Arena, gap: f32) {
+    let gap = gap.clamp(0., arena.height);
+    let segment_height = (arena.height - gap) * 0.5;
+    for direction in [-1., 1.] {
+        commands
+            .spawn()
+            .insert(ColliderKind::Obstacle)
+            .insert(ArenaFixture)
+            .insert(Gameplay)
+            .insert_bundle(SpriteBundle {
+                transform: Transform {
+                    translation: Vec3::new(0., direction * (gap + segment_height) * 0.5, 0.0),
+                    ..default()
+                },
+                sprite: Sprite {
+                    color: Color::rgb(0.8, 0.8, 0.8),
+                    custom_size: Some(Vec2::new(8., segment_height)),
+                    ..default()
+                },
+                ..default()
+            });
+    }
+}
+
+
+/// Run criteria for the physics systems, running them at a constant rate only during a match (a real
+/// one, or the attract mode demo), as many ticks each frame as the time passed calls for
+///
+/// Ticks that come due during a hit-stop are used up without running, freezing the physics. While
+/// paused, a single tick can be stepped through on request.
+#[allow(clippy::too_many_arguments)]
+pub fn run_physics_tick(
+    time: Res<Time>,
+    mut app_state: ResMut<State<AppState>>,
+    mut clock: ResMut<PhysicsClock>,
+    mut hit_stop: ResMut<HitStop>,
+    mut step_once: ResMut<StepOnce>,
+    replay_mode: Res<ReplayMode>,
+    mut replay: ResMut<Replay>,
+) -> ShouldRun {
+    if *app_state.current() == AppState::Paused && step_once.0 {
+        step_once.0 = false;
+        return ShouldRun::YesAndCheckAgain;
+    }
+    if !matches!(app_state.current(), AppState::Playing | AppState::Demo) {
+        clock.looping = false;
+        return ShouldRun::No;
+    }
+    if !clock.looping && !clock.manual {
+        clock.accumulator += time.delta_seconds_f64();
+    }
+    let tick_due = if clock.manual {
+        clock.queued_steps > 0
+    } else {
+        clock.accumulator >= TIME_STEP as f64
+    };
+    // Playing back a replay pauses wherever the recorded match was paused, holding on to the tick
+    let playing_back = *replay_mode == ReplayMode::Playback && *app_state.current() == AppState::Playing;
+    let frozen = tick_due && !hit_stop.remaining.finished();
+    let tick = tick_due && !frozen && !(playing_back && replay.take_pause());
+    if tick_due && !frozen && !tick {
+        let _ = app_state.push(AppState::Paused);
+    }
+    if frozen {
+        hit_stop.remaining.tick(Duration::from_secs_f32(TIME_STEP));
+    }
+
+    if (tick || frozen) && clock.manual {
+        clock.queued_steps -= 1;
+    } else if tick || frozen {
+        clock.accumulator -= TIME_STEP as f64;
+    }
+    clock.looping = tick || frozen;
+    if tick {
+        ShouldRun::YesAndCheckAgain
+    } else if frozen {
+        ShouldRun::NoAndCheckAgain
+    } else {
+        ShouldRun::No
+    }
+}
+
+
+/// Start a hit-stop on paddle hits, if turned on (and motion isn't reduced)
+pub fn start_hit_stop(
+    mut collision_events: EventReader<CollisionEvent>,
+    reduce_motion: Res<ReduceMotion>,
+    mut hit_stop: ResMut<HitStop>,
+) {
+    let paddle_hit = collision_events.iter().any(|event| matches!(event, CollisionEvent::PaddleBounce(_)));
+    if paddle_hit && hit_stop.duration > 0. && !reduce_motion.0 {
+        hit_stop.remaining = Timer::from_seconds(hit_stop.duration.min(MAX_HIT_STOP), false);
+    }
+}
+
+
+/// Switch between mouse and keyboard control with Tab, which also carries on a match paused by the
+/// gamepad disconnecting
+pub fn toggle_input_mode(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut input_mode: ResMut<InputMode>,
+    mut disconnect_pause: ResMut<DisconnectPause>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        *input_mode = match *input_mode {
+            InputMode::Mouse => InputMode::Keyboard,
+            InputMode::Keyboard => InputMode::Mouse,
+        };
+        if disconnect_pause.waiting {
+            disconnect_pause.waiting = false;
+            let _ = app_state.pop();
+        }
+    }
+}
+
+
+/// Tint balls from the theme's ball color (or their own multi-ball color, if tinting those too) toward
+/// its fast ball color as they speed up from the serve speed to the max speed
+pub fn tint_balls_by_speed(
+    theme: Res<Theme>,
+    game_speed: Res<GameSpeed>,
+    config: Res<GameConfig>,
+    speed_tint_ball_colors: Res<SpeedTintBallColors>,
+    mut ball_query: Query<(&mut Sprite, &Velocity, Option<&BallColor>), With<Ball>>,
+) {
+    let base_speed = config.ball_speed * game_speed.0;
+    let max_speed = config.max_ball_speed * game_speed.0;
+    for (mut sprite, velocity, ball_color) in ball_query.iter_mut() {
+        let color = ball_color.map_or(theme.ball, |ball_color| ball_color.0);
+        sprite.color = if ball_color.is_some() && !speed_tint_ball_colors.0 {
+            color
+        } else {
+            let t = ((velocity.0.length() - base_speed) / (max_speed - base_speed)).clamp(0., 1.);
+            lerp_color(color, theme.ball_fast, t)
+        };
+    }
+}
+
+
+/// Switch to the next color theme with T
+pub fn cycle_theme(keyboard_input: Res<Input<KeyCode>>, mut theme: ResMut<Theme>) {
+    if keyboard_input.just_pressed(KeyCode::T) {
+        *theme = theme.with_preset(theme.preset.next());
+    }
+}
+
+
+/// Draw the background, paddles, net and text in the theme's colors, recoloring everything when
+/// the theme changes and anything new as it's spawned
+#[allow(clippy::type_complexity)]
+pub fn apply_theme(
+    theme: Res<Theme>,
+    mut clear_color: ResMut<ClearColor>,
+    mut sprite_query: Query<
+        (&mut Sprite, Option<&Player>, Option<&Net>),
+        Or<(With<Player>, With<Opponent>, With<Net>)>,
+    >,
+    mut text_query: Query<&mut Text, With<ThemedText>>,
+) {
+    if theme.is_changed() {
+        clear_color.0 = theme.background;
+    }
+
+    for (mut sprite, player, net) in sprite_query.iter_mut() {
+        if theme.is_changed() || sprite.is_added() {
+            sprite.color = if net.is_some() {
+                theme.net
+            } else if player.is_some() {
+                theme.player_paddle()
+            } else {
+                theme.opponent_paddle()
+            };
+        }
+    }
+
+    for mut text in text_query.iter_mut() {
+        if theme.is_changed() || text.is_added() {
+            for section in text.sections.iter_mut() {
+                // Keep any translucency (e.g. the countdown's)
+                let alpha = section.style.color.a();
+                section.style.color = theme.text;
+                section.style.color.set_a(alpha);
+            }
+        }
+    }
+}
+
+
+/// Change the AI difficulty with the 1 (easy), 2 (medium) and 3 (hard) keys, taking effect immediately
+pub fn select_difficulty(keyboard_input: Res<Input<KeyCode>>, mut difficulty: ResMut<Difficulty>) {
+    if keyboard_input.just_pressed(KeyCode::Key1) {
+        *difficulty = Difficulty::Easy;
+    } else if keyboard_input.just_pressed(KeyCode::Key2) {
+        *difficulty = Difficulty::Medium;
+    } else if keyboard_input.just_pressed(KeyCode::Key3) {
+        *difficulty = Difficulty::Hard;
+    }
+}
+
+
+/// Keep track of the gamepad used for the player paddle as gamepads are (dis)connected
+///  - Losing it mid-match pauses the game (if enabled), until a gamepad reconnects
+pub fn track_gamepads(
+    mut gamepad_events: EventReader<GamepadEvent>,
+    mut active_gamepad: ResMut<ActiveGamepad>,
+    mut disconnect_pause: ResMut<DisconnectPause>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    for GamepadEvent(gamepad, event_type) in gamepad_events.iter() {
+        match event_type {
+            GamepadEventType::Connected if active_gamepad.0.is_none() => {
+                active_gamepad.0 = Some(*gamepad);
+                if disconnect_pause.waiting {
+                    disconnect_pause.waiting = false;
+                    let _ = app_state.pop();
+                }
+            },
+            // Falls back to mouse/keyboard
+            GamepadEventType::Disconnected if active_gamepad.0 == Some(*gamepad) => {
+                active_gamepad.0 = None;
+                if disconnect_pause.enabled && *app_state.current() == AppState::Playing {
+                    // Ignored if another transition (e.g. game over) is already queued
+                    disconnect_pause.waiting = app_state.push(AppState::Paused).is_ok();
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+
+/// Note when fresh input to move the player paddle arrives (the mouse moved, or a movement key
+/// pressed, depending on the input mode), while measuring input latency and not already waiting on
+/// earlier input
+pub fn timestamp_input(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut mouse_motion_events: EventReader<MouseMotion>,
+    input_mode: Res<InputMode>,
+    key_bindings: Res<KeyBindings>,
+    mut input_latency: ResMut<InputLatency>,
+) {
+    if !input_latency.measuring || input_latency.input_at.is_some() {
+        return;
+    }
+    let moved = match *input_mode {
+        InputMode::Mouse => mouse_motion_events.iter().next().is_some(),
+        _ => keyboard_input.any_just_pressed([key_bindings.move_up, key_bindings.move_down, KeyCode::Up, KeyCode::Down]),
+    };
+    if moved {
+        input_latency.input_at = time.last_update();
+    }
+}
+
+
+/// Hold on to serve presses until the next physics tick reads them
+pub fn latch_serve_presses(
+    keyboard_input: Res<Input<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut serve_presses: ResMut<ServePresses>,
+) {
+    serve_presses.player |= keyboard_input.just_pressed(key_bindings.serve);
+    serve_presses.second_player |= keyboard_input.just_pressed(KeyCode::Return);
+}
+
+
+/// Read the input for this physics tick, for every system in the simulation that reads input
+///  - Live input, also added to the replay when recording one
+///  - Or the replay's recorded input in its place, when playing one back
+///  - Nobody's input during the demo, which the AI plays on its own
+#[allow(clippy::too_many_arguments)]
+pub fn read_tick_input(
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut serve_presses: ResMut<ServePresses>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    active_gamepad: Res<ActiveGamepad>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    key_bindings: Res<KeyBindings>,
+    input_sources: Res<InputSources>,
+    replay_mode: Res<ReplayMode>,
+    mut replay: ResMut<Replay>,
+    app_state: Res<State<AppState>>,
+    mut tick_input: ResMut<TickInput>,
+) {
+    let mouse_delta_y: f32 = mouse_motion.iter().map(|motion| {
+        // Negate because delta is y-down yet world space is y-up
+        -motion.delta.y
+    }).sum();
+    let serve_presses = std::mem::take(&mut *serve_presses);
+    if *app_state.current() == AppState::Demo {
+        *tick_input = TickInput::default();
+        return;
+    }
+
+    // Analog speed proportional to stick deflection, or full speed on the D-pad
+    let gamepad_direction = |gamepad: Gamepad| {
+        let stick = gamepad_axes
+            .get(GamepadAxis(gamepad, GamepadAxisType::LeftStickY))
+            .unwrap_or(0.);
+        if stick.abs() > GAMEPAD_DEADZONE {
+            stick
+        } else if gamepad_buttons.pressed(GamepadButton(gamepad, GamepadButtonType::DPadUp)) {
+            1.
+        } else if gamepad_buttons.pressed(GamepadButton(gamepad, GamepadButtonType::DPadDown)) {
+            -1.
+        } else {
+            0.
+        }
+    };
+    // The player's own gamepad if they're assigned one, otherwise whichever is connected
+    let player_gamepad = match input_sources.player {
+        InputSource::Gamepad(gamepad) => Some(gamepad),
+        _ => active_gamepad.0,
+    };
+    let second_player_direction = match input_sources.opponent {
+        InputSource::Gamepad(gamepad) => gamepad_direction(gamepad),
+        _ => key_direction(&keyboard_input, [KeyCode::Up], [KeyCode::Down]),
+    };
+
+    // Arrow keys belong to the right paddle when it's on the keyboard, otherwise they work
+    // alongside the bound keys
+    let (up_key, down_key) = (key_bindings.move_up, key_bindings.move_down);
+    let keyboard_direction = if input_sources.opponent == InputSource::Keyboard {
+        key_direction(&keyboard_input, [up_key], [down_key])
+    } else {
+        key_direction(&keyboard_input, [up_key, KeyCode::Up], [down_key, KeyCode::Down])
+    };
+
+    let live_input = TickInput {
+        mouse_delta_y,
+        keyboard_direction,
+        gamepad_direction: player_gamepad.map_or(0., gamepad_direction),
+        second_player_direction,
+        serve: serve_presses.player,
+        second_player_serve: serve_presses.second_player,
+        catch: mouse_buttons.pressed(MouseButton::Left),
+    };
+    *tick_input = replay.next_input(*replay_mode, live_input);
+}
+
+
+/// Note where a recorded match was paused, so playing it back pauses there too
+pub fn record_pause(replay_mode: Res<ReplayMode>, mut replay: ResMut<Replay>) {
+    if *replay_mode == ReplayMode::Recording {
+        let tick = replay.inputs.len();
+        replay.pauses.push(tick);
+    }
+}
+
+
+/// Assign each paddle its input source from the input settings, whenever one of them changes
+///  - The AI plays both paddles in the demo
+///  - A paddle assigned its own source in the settings uses that
+///  - Player one is on the keyboard in two-player mode, leaving the arrow keys to the right paddle
+///  - Otherwise the player uses the mouse or keyboard, per the input mode, against the AI
+///
+/// Playing back a replay goes by the input mode it recorded, ignoring any assigned sources.
+#[allow(clippy::too_many_arguments)]
+pub fn assign_input_sources(
+    input_mode: Res<InputMode>,
+    two_player: Res<TwoPlayer>,
+    assignments: Res<InputAssignments>,
+    replay_mode: Res<ReplayMode>,
+    replay: Res<Replay>,
+    app_state: Res<State<AppState>>,
+    mut input_sources: ResMut<InputSources>,
+    mut assigned_from: Local<Option<(AppState, bool, InputMode, InputAssignments)>>,
+) {
+    let (input_mode, assignments) = match *replay_mode {
+        ReplayMode::Playback => (replay.input_mode, InputAssignments::default()),
+        _ => (*input_mode, *assignments),
+    };
+    let settings = (*app_state.current(), two_player.0, input_mode, assignments);
+    if *assigned_from == Some(settings) {
+        return;
+    }
+    *assigned_from = Some(settings);
+
+    let automatic = match settings {
+        (AppState::Demo, ..) => {
+            *input_sources = InputSources { player: InputSource::Ai, opponent: InputSource::Ai };
+            return;
+        },
+        (_, true, ..) => InputSources { player: InputSource::Keyboard, opponent: InputSource::Keyboard },
+        (_, false, InputMode::Mouse, _) => InputSources { player: InputSource::Mouse, opponent: InputSource::Ai },
+        (_, false, InputMode::Keyboard, _) => InputSources { player: InputSource::Keyboard, opponent: InputSource::Ai },
+    };
+    *input_sources = InputSources {
+        player: assignments.player.unwrap_or(automatic.player),
+        opponent: assignments.opponent.unwrap_or(automatic.opponent),
+    };
+}
+
+
+/// Drives each paddle from the input source assigned to its side
+///  - The mouse is followed directly, the hand already gives it momentum
+///  - Keys and gamepads build up to a constant speed while held, the player's gamepad taking over
+///    from their mouse or keyboard while it's in use
+///  - The AI tracks the ball
+///  - A networked paddle holds still, nothing sends its input yet
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn paddle_controller(
+    ball_query: Query<(&Transform, &Velocity), With<Ball>>,
+    mut paddle_query: Query<
+        (&Transform, &mut Velocity, Option<&mut Stamina>, Option<&Opponent>),
+        (Or<(With<Player>, With<Opponent>)>, Without<Ball>),
+    >,
+    input_sources: Res<InputSources>,
+    tick_input: Res<TickInput>,
+    travel_fraction: Res<PlayerTravelFraction>,
+    mut ai: AiControl,
+    mut opponent_target: ResMut<OpponentTarget>,
+    mut input_latency: ResMut<InputLatency>,
+    // What the AI has seen of the ball, for the player's paddle then the opponent's
+    mut observations: Local<[VecDeque<Option<(Vec2, Vec2)>>; 2]>,
+) {
+    let config = *ai.config;
+    for (transform, mut velocity, mut stamina, opponent) in paddle_query.iter_mut() {
+        let is_opponent = opponent.is_some();
+        let source = if is_opponent { input_sources.opponent } else { input_sources.player };
+        let observations = &mut observations[is_opponent as usize];
+        let speed_factor = stamina.as_ref().map_or(1., |stamina| stamina.speed_factor());
+
+        if source != InputSource::Ai {
+            observations.clear();
+            // Only the opponent's target is shown in the debug overlay
+            if is_opponent {
+                opponent_target.0 = None;
+            }
+        }
+        match source {
+            InputSource::Ai => {
+                let (target_y, ai_velocity) =
+                    ai.paddle_velocity(&ball_query, transform, velocity.0.y, speed_factor, observations);
+                velocity.0.y = ai_velocity;
+                if is_opponent {
+                    opponent_target.0 = Some(target_y);
+                }
+                if let Some(stamina) = stamina.as_mut() {
+                    stamina.update(velocity.0.y * TIME_STEP);
+                }
+                continue;
+            },
+            InputSource::Network => {
+                velocity.0.y = 0.;
+                continue;
+            },
+            _ => (),
+        }
+
+        // Arrow keys (or the second player's gamepad) on the right, the bound keys on the left
+        let gamepad_in_use = !is_opponent && tick_input.gamepad_direction != 0.;
+        let direction = match source {
+            InputSource::Mouse if !gamepad_in_use => None,
+            _ if is_opponent => Some(tick_input.second_player_direction),
+            InputSource::Keyboard if !gamepad_in_use => Some(tick_input.keyboard_direction),
+            _ => Some(tick_input.gamepad_direction),
+        };
+        let top_speed = config.player_paddle_speed * speed_factor;
+        let delta_y = match direction {
+            Some(direction) => accelerate_paddle(&config, velocity.0.y, direction * top_speed) * TIME_STEP,
+            None => tick_input.mouse_delta_y * speed_factor,
+        };
+
+        // Prevent paddle going off-screen (or out of the player's restricted zone)
+        let bound = paddle_bound(&ai.arena, &config, if is_opponent { 1.0 } else { travel_fraction.0 });
+        let new_position = (transform.translation.y + delta_y).clamp(-bound, bound);
+        if let Some(stamina) = stamina.as_mut() {
+            stamina.update(new_position - transform.translation.y);
+        }
+        // Moved by apply_velocity, so the paddle's velocity reflects its frame-to-frame movement
+        velocity.0.y = (new_position - transform.translation.y) / TIME_STEP;
+
+        if !is_opponent && velocity.0.y != 0. {
+            if let Some(input_at) = input_latency.input_at.take() {
+                input_latency.measured = Some(input_at.elapsed());
+            }
+        }
+    }
+}
+
+
+/// Paddle Y velocity after one tick of changing from `velocity` toward `target`, limited by the
+/// paddle's acceleration (and helped by friction when slowing down)
+fn accelerate_paddle(config: &GameConfig, velocity: f32, target: f32) -> f32 {
+    let slowing = target.abs() < velocity.abs() || target * velocity < 0.;
+    let accel = if slowing { config.paddle_max_accel + config.paddle_friction } else { config.paddle_max_accel };
+    let max_change = accel * TIME_STEP;
+    velocity + (target - velocity).clamp(-max_change, max_change)
+}
+
+
+/// Direction (-1, 0 or 1) along the Y axis from whichever of the up/down keys are held
+fn key_direction<const N: usize>(
+    keyboard_input: &Input<KeyCode>,
+    up_keys: [KeyCode; N],
+    down_keys: [KeyCode; N],
+) -> f32 {
+    let mut direction = 0.;
+    if keyboard_input.any_pressed(up_keys) {
+        direction += 1.;
+    }
+    if keyboard_input.any_pressed(down_keys) {
+        direction -= 1.;
+    }
+    direction
+}
+
+
+/// Furthest a paddle's center may travel from the middle of the arena, given the fraction of the
+/// arena height it's allowed to use
+fn paddle_bound(arena: &Arena, config: &GameConfig, travel_fraction: f32) -> f32 {
+    let half_travel = arena.height * 0.5 * travel_fraction.clamp(0., 1.);
+    (half_travel - (config.paddle_size.y * 0.5) - 5.).max(0.)
+}
+
+
+/// Generic system to apply velocity to any entity with velocity and transform components, slowed
+/// down by the time scale
+pub fn apply_velocity(mut query: Query<(&mut Transform, &Velocity)>, time_scale: Res<TimeScale>) {
+    let step = TIME_STEP * time_scale.0;
+    for (mut transform, velocity) in query.iter_mut() {
+        transform.translation.x += velocity.0.x * step;
+        transform.translation.y += velocity.0.y * step;
+    }
+}
+
+
+/// Ease into slow motion while a ball is in play on match point, and back to full speed once the
+/// point is over
+pub fn update_time_scale(
+    time: Res<Time>,
+    scoreboard: Res<Scoreboard>,
+    winning_score: Res<WinningScore>,
+    win_by_two: Res<WinByTwo>,
+    endless: Res<Endless>,
+    ball_query: Query<(), With<Ball>>,
+    mut time_scale: ResMut<TimeScale>,
+) {
+    let match_point = is_match_point(&scoreboard, &winning_score, &win_by_two, &endless);
+    let target = if match_point && !ball_query.is_empty() { MATCH_POINT_TIME_SCALE } else { 1. };
+    if time_scale.0 != target {
+        let max_change = TIME_SCALE_EASE_RATE * time.delta_seconds();
+        time_scale.0 += (target - time_scale.0).clamp(-max_change, max_change);
+    }
+}
+
+
+/// Every so often, if power-ups are enabled and none is waiting to be collected, spawn one at a
+/// random spot clear of the net and gutters
+pub fn spawn_power_ups(
+    mut power_ups: ResMut<PowerUps>,
+    power_up_query: Query<(), With<PowerUp>>,
+    arena: Res<Arena>,
+    mut rng: ResMut<GameRng>,
+    mut commands: Commands,
+) {
+    let spawn_due = power_ups.spawn_timer.tick(Duration::from_secs_f32(TIME_STEP)).just_finished();
+    if !power_ups.enabled || !spawn_due || !power_up_query.is_empty() {
+        return;
+    }
+
+    let x = rng.0.gen_range(40. ..arena.width * 0.5 - 80.) * if rng.0.gen_bool(0.5) { 1. } else { -1. };
+    let y = rng.0.gen_range(-arena.height * 0.5 + 40. ..arena.height * 0.5 - 40.);
+
+    commands
+        .spawn()
+        .insert(PowerUp)
+        .insert(ColliderKind::PowerUp)
+        .insert(Gameplay)
+        .insert_bundle(SpriteBundle {
+            transform: Transform {
+                translation: Vec3::new(x, y, 0.0),
+                ..default()
+            },
+            sprite: Sprite {
+                color: Color::rgb(0.3, 0.9, 0.4),
+                custom_size: Some(POWER_UP_SIZE),
+                ..default()
+            },
+            ..default()
+        });
+}
+
+
+/// Set out a fresh column of targets in the opponent's half once they've all been knocked out, in
+/// target practice
+pub fn restock_targets(
+    target_practice: Res<TargetPractice>,
+    target_query: Query<(), With<Target>>,
+    arena: Res<Arena>,
+    mut commands: Commands,
+) {
+    if !target_practice.0 || !target_query.is_empty() {
+        return;
+    }
+
+    for index in 0..TARGET_COUNT {
+        let y = arena.height * ((index + 1) as f32 / (TARGET_COUNT + 1) as f32 - 0.5);
+        commands
+            .spawn()
+            .insert(Target)
+            .insert(ColliderKind::Target)
+            .insert(Gameplay)
+            .insert_bundle(SpriteBundle {
+                transform: Transform {
+                    translation: Vec3::new(arena.width * 0.2, y, 0.0),
+                    ..default()
+                },
+                sprite: Sprite {
+                    color: Color::rgb(0.9, 0.3, 0.3),
+                    custom_size: Some(TARGET_SIZE),
+                    ..default()
+                },
+                ..default()
+            });
+    }
+}
+
+
+/// Grow paddles that have just been enlarged by collecting a power-up
+pub fn grow_enlarged_paddles(
+    mut paddle_query: Query<&mut Sprite, Added<Enlarged>>,
+    config: Res<GameConfig>,
+) {
+    for mut sprite in paddle_query.iter_mut() {
+        sprite.custom_size = Some(config.paddle_size * Vec2::new(1., POWER_UP_PADDLE_SCALE));
+    }
+}
+
+
+/// Shrink enlarged paddles back to normal size once their power-up runs out
+pub fn expire_enlarged_paddles(
+    mut paddle_query: Query<(Entity, &mut Sprite, &mut Enlarged)>,
+    config: Res<GameConfig>,
+    mut commands: Commands,
+) {
+    for (paddle, mut sprite, mut enlarged) in paddle_query.iter_mut() {
+        if enlarged.0.tick(Duration::from_secs_f32(TIME_STEP)).finished() {
+            sprite.custom_size = Some(config.paddle_size);
+            commands.entity(paddle).remove::<Enlarged>();
+        }
+    }
+}
+
+
+/// Resolve rallies that won't end on their own
+///  - A ball travelling almost horizontally for too long is nudged by a random Y speed
+///  - A ball that stays in one spot for too long (e.g. wedged against a wall) is despawned, and once
+///    no balls are left the rally is over and the ball is served again
+#[allow(clippy::type_complexity)]
+pub fn stuck_detector(
+    mut ball_query: Query<(Entity, &Transform, &mut Velocity, &mut StuckDetector), (With<Ball>, Without<Caught>)>,
+    all_balls_query: Query<(), With<Ball>>,
+    mut ball_spawn_timer: ResMut<BallSpawnTimer>,
+    mut match_stats: ResMut<MatchStats>,
+    mut rng: ResMut<GameRng>,
+    mut commands: Commands,
+) {
+    // Including any held by the sticky paddle
+    let mut live_balls = all_balls_query.iter().count();
+
+    for (ball, transform, mut velocity, mut detector) in ball_query.iter_mut() {
+        if velocity.0.y.abs() < STUCK_MIN_Y_SPEED {
+            detector.flat_time += TIME_STEP;
+        } else {
+            detector.flat_time = 0.;
+        }
+        if detector.flat_time > STUCK_FLAT_TIME {
+            let nudge = rng.0.gen_range(STUCK_NUDGE_SPEED * 0.5..=STUCK_NUDGE_SPEED);
+            velocity.0.y += if rng.0.gen_bool(0.5) { nudge } else { -nudge };
+            detector.flat_time = 0.;
+        }
+
+        let position = transform.translation.truncate();
+        if position.distance(detector.anchor) > STUCK_MIN_MOVEMENT {
+            detector.anchor = position;
+            detector.still_time = 0.;
+        } else {
+            detector.still_time += TIME_STEP;
+        }
+        if detector.still_time > STUCK_STILL_TIME {
+            commands.entity(ball).despawn();
+            live_balls -= 1;
+            if live_balls == 0 {
+                ball_spawn_timer.0.reset();
+                match_stats.rallies += 1;
+            }
+        }
+    }
+}
+
+
+/// Swept collision along the X axis: whether a ball travelling from `previous` to `current` crossed
+/// the near side of the box on the way, and if so the X position at which it first touched it
+fn swept_collide_x(previous: Vec3, current: Vec3, ball_size: Vec2, box_pos: Vec3, box_size: Vec2) -> Option<f32> {
+    let half_extents = (ball_size + box_size) * 0.5;
+    let travel = current - previous;
+    if travel.x == 0. {
+        return None;
+    }
+
+    // Ball's center position when touching the side of the box facing it
+    let contact_x = if travel.x > 0. {
+        box_pos.x - half_extents.x
+    } else {
+        box_pos.x + half_extents.x
+    };
+    let t = (contact_x - previous.x) / travel.x;
+    if !(0. ..=1.).contains(&t) {
+        return None;
+    }
+
+    let contact_y = previous.y + travel.y * t;
+    ((contact_y - box_pos.y).abs() < half_extents.y).then_some(contact_x)
+}
+
+
+/// Curve the path of balls passing near a magnet, keeping their speed unchanged
+pub fn apply_magnets(
+    magnet_query: Query<(&Magnet, &Transform)>,
+    mut ball_query: Query<(&Transform, &mut Velocity), With<Ball>>,
+) {
+    for (ball_transform, mut ball_velocity) in ball_query.iter_mut() {
+        let speed = ball_velocity.0.length();
+
+        for (magnet, magnet_transform) in magnet_query.iter() {
+            let offset = (magnet_transform.translation - ball_transform.translation).truncate();
+            let distance = offset.length();
+            if distance >= magnet.radius || distance == 0. {
+                continue;
+            }
+
+            // Pull is strongest close to the magnet, fading to nothing at its radius
+            let falloff = 1. - distance / magnet.radius;
+            let nudge = (magnet.strength * falloff * TIME_STEP).clamp(-MAGNET_MAX_NUDGE, MAGNET_MAX_NUDGE);
+            ball_velocity.0 += offset / distance * nudge;
+        }
+
+        // Only change direction, not speed
+        ball_velocity.0 = ball_velocity.0.normalize_or_zero() * speed;
+    }
+}
+
+
+/// Detect ball collisions and act accordingly
+///  - Bounce off walls, paddles and obstacles
+///  - Knock out targets, and collect power-ups for the paddle that last touched the ball
+///  - Increment scores if hit goals
+///  - Play sounds
+///
+/// Simultaneous overlaps (e.g. near arena corners) resolve in a fixed priority: wall bounce first,
+/// then paddle bounce, then goal. A ball bounced by a paddle can't score on the same tick.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn process_collisions(
+    mut ball_query: Query<
+        (Entity, &mut Velocity, &mut Transform, &mut Sprite, &mut Serve, Option<&Caught>, Option<&LastTouched>, Option<&BallColor>),
+        With<Ball>,
+    >,
+    collider_query: Query<(Entity, &Transform, &Sprite, &ColliderKind, Option<&Player>, Option<&Velocity>), Without<Ball>>,
+    mut ball_spawn_timer: ResMut<BallSpawnTimer>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut player_turn: ResMut<PlayerTurn>,
+    mut onboarding: ResMut<Onboarding>,
+    rules: MatchRules,
+    tick_input: Res<TickInput>,
+    time_scale: Res<TimeScale>,
+    mut rally_count: ResMut<RallyCount>,
+    mut match_stats: ResMut<MatchStats>,
+    arena: Res<Arena>,
+    mut collision_events: EventWriter<CollisionEvent>,
+    mut rally_lets: EventWriter<RallyLet>,
+    mut comebacks: EventWriter<Comeback>,
+    mut commands: Commands,
+) {
+    // Balls still in play (including any held by the sticky paddle), so a new serve only comes once all are gone
+    let mut live_balls = ball_query.iter().count();
+
+    // Power-ups collected this tick, so two balls reaching the same one can't both collect it
+    let mut collected_power_ups = Vec::new();
+
+    // Multi-ball colors of the balls in play, so each new ball gets one of its own
+    let mut ball_colors: Vec<Color> = ball_query.iter().filter_map(|(.., ball_color)| ball_color.map(|color| color.0)).collect();
+
+    for (ball, mut ball_velocity, mut ball_transform, mut ball_sprite, mut serve, caught, last_touched, _) in ball_query.iter_mut() {
+        if caught.is_some() {
+            continue;
+        }
+        let ball_size = ball_sprite.custom_size.unwrap();
+        // Where the ball was before this step's movement, for swept collision checks
+        let previous_translation = ball_transform.translation - ball_velocity.0.extend(0.) * TIME_STEP * time_scale.0;
+
+        // Top/bottom walls (bounce)
+        let top_wall_collision = collide(
+            ball_transform.translation,
+            ball_size,
+            Vec3::new(0., -arena.height * 0.5 - 20., 0.),
+            Vec2::new(arena.width, 40.),
+        );
+        let bottom_wall_collision = collide(
+            ball_transform.translation,
+            ball_size,
+            Vec3::new(0., arena.height * 0.5 + 20., 0.),
+            Vec2::new(arena.width, 40.),
+        );
+        if top_wall_collision.is_some() || bottom_wall_collision.is_some() {
+            ball_velocity.0.y = -ball_velocity.0.y;
+            match_stats.wall_bounces += 1;
+            collision_events.send(CollisionEvent::WallBounce);
+        }
+
+        // Iterate over other colliders, responding according to their kind
+        let mut hit_paddle = false;
+        for (collider, transform, sprite, kind, player, paddle_velocity) in collider_query.iter() {
+            let collision = collide(
+                ball_transform.translation,
+                ball_size,
+                transform.translation,
+                sprite.custom_size.unwrap(),
+            );
+
+            match kind {
+                // Obstacle (deflect straight back off whichever side was hit)
+                ColliderKind::Obstacle => {
+                    let (x, y) = (ball_velocity.0.x, ball_velocity.0.y);
+                    let obstacle_size = sprite.custom_size.unwrap();
+                    let half_extents = (ball_size + obstacle_size) * 0.5;
+                    let offset = transform.translation - previous_translation;
+                    let collision = match collision {
+                        // A fast ball can already be well into (or out the far side of) a thin obstacle,
+                        // so go by which side it came from, moving it back out that side
+                        Some(Collision::Left | Collision::Right | Collision::Inside) if offset.x.abs() >= half_extents.x => {
+                            let side = -offset.x.signum();
+                            ball_transform.translation.x = transform.translation.x + side * half_extents.x;
+                            Some(if side < 0. { Collision::Left } else { Collision::Right })
+                        }
+                        Some(Collision::Inside) => {
+                            let side = -offset.y.signum();
+                            ball_transform.translation.y = transform.translation.y + side * half_extents.y;
+                            Some(if side < 0. { Collision::Bottom } else { Collision::Top })
+                        }
+                        // Fast balls can pass right through a thin obstacle in one step, so also
+                        // sweep along the path travelled, moving the ball back to where it made contact
+                        None => swept_collide_x(
+                            previous_translation,
+                            ball_transform.translation,
+                            ball_size,
+                            transform.translation,
+                            obstacle_size,
+                        ).map(|contact_x| {
+                            ball_transform.translation.x = contact_x;
+                            if x > 0. { Collision::Left } else { Collision::Right }
+                        }),
+                        collision => collision,
+                    };
+                    let deflected = match collision {
+                        Some(Collision::Left) => Vec2::new(-x.abs(), y),
+                        Some(Collision::Right) => Vec2::new(x.abs(), y),
+                        Some(Collision::Top) => Vec2::new(x, y.abs()),
+                        Some(Collision::Bottom) => Vec2::new(x, -y.abs()),
+                        _ => continue,
+                    };
+                    // Only bounce if moving into the obstacle, not while still separating from it
+                    if deflected != ball_velocity.0 {
+                        ball_velocity.0 = deflected;
+                        collision_events.send(CollisionEvent::WallBounce);
+                    }
+                },
+                // Target (knock it out, leaving the ball's course unchanged)
+                ColliderKind::Target => {
+                    if collision.is_some() {
+                        commands.entity(collider).despawn();
+                    }
+                },
+                // Power-up (collect it for the paddle that last touched the ball, if any)
+                ColliderKind::PowerUp => {
+                    let last_touched = match (collision, last_touched) {
+                        (Some(_), Some(last_touched)) if !collected_power_ups.contains(&collider) => last_touched,
+                        _ => continue,
+                    };
+                    collected_power_ups.push(collider);
+                    commands.entity(collider).despawn();
+                    // Collecting another while enlarged restarts the duration
+                    commands.entity(last_touched.0).insert(Enlarged(Timer::from_seconds(POWER_UP_DURATION, false)));
+                },
+                // Paddle (bounce)
+                ColliderKind::Paddle => {
+                    let catch_ball = rules.sticky_paddle.0
+                        && player.is_some()
+                        && tick_input.catch;
+                    let ball_heading_right = ball_velocity.0.x > 0.;
+
+                    let mut bounce_off_paddle = || {
+                        let dst_from_center = ball_transform.translation.y - transform.translation.y;
+                        // On the face of the paddle the ball is heading into
+                        let contact = Vec2::new(
+                            transform.translation.x - ball_velocity.0.x.signum() * sprite.custom_size.unwrap().x * 0.5,
+                            ball_transform.translation.y,
+                        );
+                        commands.entity(ball).insert(LastTouched(collider));
+                        if catch_ball {
+                            // Hold the ball against the paddle until the button is released
+                            ball_velocity.0 = Vec2::ZERO;
+                            commands.entity(ball).insert(Caught {
+                                offset_y: dst_from_center,
+                                hold_timer: Timer::from_seconds(STICKY_MAX_HOLD, false),
+                            });
+                            return;
+                        }
+
+                        // Reflect mode keeps the ball's speed, so nothing below speeds it up and spin
+                        // only changes its direction
+                        let reflect = *rules.bounce_mode == BounceMode::Reflect;
+                        let incoming_speed = ball_velocity.0.length();
+
+                        // Speed up a little with each hit of the rally, up to a cap
+                        let next_count = rally_count.0.saturating_add(1);
+                        let config = &rules.config;
+                        let ramp = if reflect {
+                            1.
+                        } else if config.rally_speed_ramp.powi(next_count as i32) <= config.rally_max_speed_factor {
+                            config.rally_speed_ramp
+                        } else {
+                            1.
+                        };
+                        rally_count.0 = next_count;
+                        match_stats.longest_rally = match_stats.longest_rally.max(next_count);
+                        ball_velocity.0.x = -ball_velocity.0.x * ramp;
+
+                        // The longer the rally, the smaller (and harder to hit) the ball gets
+                        let scale = (1. - config.ball_shrink_per_hit * next_count as f32).max(config.min_ball_scale);
+                        ball_sprite.custom_size = Some(config.ball_size * scale);
+
+                        // Long rallies bring another ball into play, served from the center away from the hitter.
+                        // With the table already full, the hitting ball is sped up instead
+                        if rules.multi_ball.enabled && next_count.is_multiple_of(MULTI_BALL_HITS_PER_BALL) {
+                            if live_balls < rules.multi_ball.max_balls {
+                                let color = MULTI_BALL_PALETTE
+                                    .into_iter()
+                                    .find(|color| !ball_colors.contains(color))
+                                    .unwrap_or(MULTI_BALL_PALETTE[live_balls % MULTI_BALL_PALETTE.len()]);
+                                ball_colors.push(color);
+                                spawn_ball(
+                                    &mut commands,
+                                    config,
+                                    Vec2::ZERO,
+                                    Vec2::new(ball_velocity.0.x, 0.),
+                                    Serve {
+                                        immunity: Timer::from_seconds(SERVE_GUTTER_IMMUNITY, false),
+                                        by_player: player.is_some(),
+                                    },
+                                    Some(color),
+                                );
+                                live_balls += 1;
+                            } else if !reflect {
+                                ball_velocity.0.x *= MULTI_BALL_CAPPED_BOOST;
+                            }
+                        }
+
+                        // Reflect mode keeps the Y-velocity, mirroring the incoming angle
+                        if !reflect {
+                            // Determine Y-velocity based on where on the paddle it hit
+                            ball_velocity.0.y = dst_from_center * config.bounce_angle_multiplier * rules.game_speed.0;
+                        }
+                        // A moving paddle throws the ball along with it
+                        let paddle_velocity_y = paddle_velocity.map_or(0., |velocity| velocity.0.y);
+                        let max_y_speed = config.max_bounce_y_speed * rules.game_speed.0;
+                        ball_velocity.0.y = (ball_velocity.0.y + paddle_velocity_y * config.paddle_spin_factor)
+                            .clamp(-max_y_speed, max_y_speed);
+                        if reflect {
+                            ball_velocity.0 = ball_velocity.0.normalize_or_zero() * incoming_speed;
+                        }
+                        collision_events.send(CollisionEvent::PaddleBounce(contact));
+                    };
+
+                    // Bounce back off the face of the paddle, or glance off its top or bottom edge
+                    if let Some(Collision::Left | Collision::Right | Collision::Inside) = collision {
+                        // Which side of the paddle the ball came from: -1 for left, 1 for right (a
+                        // fast ball can already be past the paddle's middle, or straddling it)
+                        let side = if previous_translation.x < transform.translation.x { -1. } else { 1. };
+                        // A ball already heading away (bounced last tick) only needs separating
+                        if ball_heading_right == (side < 0.) {
+                            bounce_off_paddle();
+                        }
+                        // Move the ball out to just beside the paddle, so the overlap can't carry
+                        // over into the next tick and bounce it straight back again
+                        let paddle_size = sprite.custom_size.unwrap();
+                        ball_transform.translation.x = transform.translation.x + side * (paddle_size.x + ball_size.x) * 0.5;
+                        hit_paddle = true;
+                    } else if let Some(Collision::Top | Collision::Bottom) = collision {
+                        // Push the ball back out past the edge so it can't clip through (or get
+                        // stuck overlapping), heading away at least as fast as the paddle moves
+                        let side = if let Some(Collision::Top) = collision { 1. } else { -1. };
+                        let paddle_size = sprite.custom_size.unwrap();
+                        ball_transform.translation.y = transform.translation.y + side * (paddle_size.y + ball_size.y) * 0.5;
+                        let paddle_velocity_y = paddle_velocity.map_or(0., |velocity| velocity.0.y);
+                        ball_velocity.0.y = side * ball_velocity.0.y.abs().max(paddle_velocity_y * side);
+
+                        commands.entity(ball).insert(LastTouched(collider));
+                        let contact = Vec2::new(
+                            ball_transform.translation.x,
+                            ball_transform.translation.y - side * ball_size.y * 0.5,
+                        );
+                        collision_events.send(CollisionEvent::PaddleBounce(contact));
+                        hit_paddle = true;
+                    } else if collision.is_none() {
+                        // Fast balls can pass right through a paddle in one step, so also sweep
+                        // along the path travelled, moving the ball back to where it made contact
+                        let swept_collision = swept_collide_x(
+                            previous_translation,
+                            ball_transform.translation,
+                            ball_size,
+                            transform.translation,
+                            sprite.custom_size.unwrap(),
+                        );
+                        if let Some(contact_x) = swept_collision {
+                            bounce_off_paddle();
+                            hit_paddle = true;
+                            ball_transform.translation.x = contact_x;
+                        }
+                    }
+                },
+            }
+        }
+
+        // Cap the overall speed rather than each axis, so steep angles can't exceed it either
+        ball_velocity.0 = ball_velocity.0.clamp_length_max(rules.config.max_ball_speed * rules.game_speed.0);
+        match_stats.fastest_ball_speed = match_stats.fastest_ball_speed.max(ball_velocity.0.length());
+
+        // Gutters (goal), unless the ball was saved by a paddle this tick
+        if hit_paddle {
+            // A rally that has gone on too long is called off as a let, served again with no point scored
+            if rules.config.max_rally_length.is_some_and(|max_length| rally_count.0 > max_length) {
+                commands.entity(ball).despawn();
+                live_balls -= 1;
+                if live_balls == 0 {
+                    ball_spawn_timer.0.reset();
+                    match_stats.rallies += 1;
+                }
+                rally_lets.send(RallyLet);
+            }
+            continue;
+        }
+        // Mirror images of each other, straddling the side edges
+        let gutter_x = arena.width * 0.5 - GUTTER_INSET;
+        let gutter_size = Vec2::new(GUTTER_WIDTH, arena.height);
+        let left_gutter_collision = collide(
+            ball_transform.translation,
+            ball_size,
+            Vec3::new(-gutter_x, 0., 0.),
+            gutter_size,
+        );
+        let right_gutter_collision = collide(
+            ball_transform.translation,
+            ball_size,
+            Vec3::new(gutter_x, 0., 0.),
+            gutter_size,
+        );
+        // A fresh serve can't score against its own server, it bounces back into play instead
+        let serve_immune = !serve.immunity.tick(Duration::from_secs_f32(TIME_STEP)).finished();
+        if left_gutter_collision.is_some() && serve_immune && serve.by_player {
+            ball_velocity.0.x = ball_velocity.0.x.abs();
+        } else if left_gutter_collision.is_some() {
+            commands.entity(ball).despawn();
+            live_balls -= 1;
+            if live_balls == 0 {
+                ball_spawn_timer.0.reset();
+                match_stats.rallies += 1;
+            }
+            if rules.comeback_gap.is_comeback(scoreboard.opponent, scoreboard.player) {
+                comebacks.send(Comeback);
+            }
+            scoreboard.opponent += 1;
+            if *rules.serve_rule == ServeRule::ScorerServes {
+                // Opponent serves, so ball heads toward player
+                player_turn.0 = true;
+            }
+            collision_events.send(CollisionEvent::Goal);
+        }
+        // In practice mode the player can't score either, the ball is always sent back
+        if right_gutter_collision.is_some() && (rules.practice_mode.0 || (serve_immune && !serve.by_player)) {
+            ball_velocity.0.x = -ball_velocity.0.x.abs();
+        } else if right_gutter_collision.is_some() {
+            commands.entity(ball).despawn();
+            live_balls -= 1;
+            if live_balls == 0 {
+                ball_spawn_timer.0.reset();
+                match_stats.rallies += 1;
+            }
+            if rules.comeback_gap.is_comeback(scoreboard.player, scoreboard.opponent) {
+                comebacks.send(Comeback);
+            }
+            scoreboard.player += 1;
+            // Player has got the hang of it
+            onboarding.served = onboarding.serves;
+            if *rules.serve_rule == ServeRule::ScorerServes {
+                // Player serves, so ball heads toward opponent
+                player_turn.0 = false;
+            }
+            collision_events.send(CollisionEvent::Goal);
+        }
+    }
+}
+
+
+/// Keep a caught ball stuck to the player paddle, launching it when the button is released
+/// or the max hold time runs out
+pub fn sticky_paddle(
+    mut ball_query: Query<(Entity, &mut Transform, &mut Velocity, &mut Caught), Without<Player>>,
+    player_query: Query<&Transform, With<Player>>,
+    tick_input: Res<TickInput>,
+    game_speed: Res<GameSpeed>,
+    config: Res<GameConfig>,
+    mut collision_events: EventWriter<CollisionEvent>,
+    mut commands: Commands,
+) {
+    let player_transform = player_query.single();
+
+    for (ball, mut ball_transform, mut ball_velocity, mut caught) in ball_query.iter_mut() {
+        // Follow the paddle, resting just in front of it
+        ball_transform.translation.x = player_transform.translation.x
+            + (config.paddle_size.x + config.ball_size.x) * 0.5 + 1.;
+        ball_transform.translation.y = player_transform.translation.y + caught.offset_y;
+
+        caught.hold_timer.tick(Duration::from_secs_f32(TIME_STEP));
+        if tick_input.catch && !caught.hold_timer.finished() {
+            continue;
+        }
+
+        // Launch, aimed by where on the paddle the ball is held
+        ball_velocity.0 = Vec2::new(
+            config.ball_speed * game_speed.0,
+            caught.offset_y * config.bounce_angle_multiplier * game_speed.0,
+        );
+        commands.entity(ball).remove::<Caught>();
+        let contact = Vec2::new(
+            player_transform.translation.x + config.paddle_size.x * 0.5,
+            ball_transform.translation.y,
+        );
+        collision_events.send(CollisionEvent::PaddleBounce(contact));
+    }
+}
+
+
+/// Spawn the ball, alternating direction, based on fixed spawn timer
+#[allow(clippy::too_many_arguments)]
+pub fn ball_spawner(
+    mut commands: Commands,
+    mut ball_spawn_timer: ResMut<BallSpawnTimer>,
+    mut player_turn: ResMut<PlayerTurn>,
+    game_speed: Res<GameSpeed>,
+    mut ai_handicap: ResMut<AiHandicap>,
+    mut onboarding: ResMut<Onboarding>,
+    mut rally_count: ResMut<RallyCount>,
+    countdown_query: Query<Entity, With<Countdown>>,
+    tick_input: Res<TickInput>,
+    mut serve_style: ServeStyle,
+    two_player: Res<TwoPlayer>,
+    key_bindings: Res<KeyBindings>,
+    config: Res<GameConfig>,
+    multi_ball: Res<MultiBall>,
+    arena: Res<Arena>,
+    mut rng: ResMut<GameRng>,
+) {
+    // Ball heads toward the player on the opponent's (or second player's) serve
+    let serve_pressed = if player_turn.0 { tick_input.second_player_serve } else { tick_input.serve };
+    let serve_now = match serve_key(&player_turn, &two_player, &serve_style.serve_on_demand, &key_bindings) {
+        // Bypass the timer, serving as soon as the server is ready
+        Some(_) if !ball_spawn_timer.0.finished() && serve_pressed => {
+            let duration = ball_spawn_timer.0.duration();
+            ball_spawn_timer.0.tick(duration);
+            true
+        },
+        Some(_) => false,
+        None => ball_spawn_timer.0.tick(Duration::from_secs_f32(TIME_STEP)).just_finished(),
+    };
+
+    if serve_now {
+        for countdown in countdown_query.iter() {
+            commands.entity(countdown).despawn();
+        }
+
+        // Determine which direction ball starts
+        let dir_multiplier = if player_turn.0 { -1.0 } else { 1.0 };
+
+        // Slower serves while onboarding, faster as endless mode ramps up
+        let speed_factor = onboarding.serve_speed_factor() * serve_style.endless.serve_boost();
+        let speed = config.ball_speed * game_speed.0 * speed_factor;
+        onboarding.served = onboarding.served.saturating_add(1);
+
+        // Straight across, or angled up/down within the serve cone
+        let random_serve = &serve_style.random_serve;
+        let mut angle = if random_serve.enabled {
+            rng.0.gen_range(-random_serve.max_angle..=random_serve.max_angle)
+        } else {
+            0.
+        };
+
+        // From the middle, or the next corner of the server's side, heading away from its wall
+        let position = match *serve_style.serve_spawn {
+            ServeSpawn::Center => Vec2::ZERO,
+            ServeSpawn::Corners { top } => {
+                let side_y = if top { 1. } else { -1. };
+                angle = -side_y * CORNER_SERVE_ANGLE;
+                *serve_style.serve_spawn = ServeSpawn::Corners { top: !top };
+                Vec2::new(-dir_multiplier * arena.width * 0.25, side_y * (arena.height * 0.5 - CORNER_SERVE_INSET))
+            },
+        };
+
+        // Spawn ball
+        spawn_ball(
+            &mut commands,
+            &config,
+            position,
+            Vec2::new(angle.cos() * dir_multiplier, angle.sin()) * speed,
+            Serve {
+                immunity: Timer::from_seconds(SERVE_GUTTER_IMMUNITY, false),
+                // Ball heads away from whoever served it
+                by_player: !player_turn.0,
+            },
+            // First of the multi-ball colors, with none in play yet
+            multi_ball.enabled.then(|| MULTI_BALL_PALETTE[0]),
+        );
+
+        // Start a new rally
+        ai_handicap.ramp.reset();
+        rally_count.0 = 0;
+
+        // Switch turns
+        player_turn.0 = !player_turn.0;
+    }
+}
+
+
+/// Which key the server must press to serve, if serving on demand and the server is human
+fn serve_key(
+    player_turn: &PlayerTurn,
+    two_player: &TwoPlayer,
+    serve_on_demand: &ServeOnDemand,
+    key_bindings: &KeyBindings,
+) -> Option<KeyCode> {
+    if !serve_on_demand.0 {
+        return None;
+    }
+    // Ball heads toward the player on the opponent's serve
+    match (player_turn.0, two_player.0) {
+        (false, _) => Some(key_bindings.serve),
+        (true, true) => Some(KeyCode::Return),
+        // AI always serves automatically
+        (true, false) => None,
+    }
+}
+
+
+/// Show the seconds left until the next serve in the middle of the arena, while waiting for it
+/// (or a prompt to serve, when waiting on the server instead)
+#[allow(clippy::too_many_arguments)]
+pub fn countdown_display(
+    ball_spawn_timer: Res<BallSpawnTimer>,
+    player_turn: Res<PlayerTurn>,
+    two_player: Res<TwoPlayer>,
+    serve_on_demand: Res<ServeOnDemand>,
+    key_bindings: Res<KeyBindings>,
+    ui_scale: Res<UiScale>,
+    mut countdown_query: Query<(&mut Text, &mut ScaledText), With<Countdown>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    if ball_spawn_timer.0.finished() {
+        return;
+    }
+
+    let (value, font_size) = match serve_key(&player_turn, &two_player, &serve_on_demand, &key_bindings) {
+        Some(key) => (format!("{} to serve", key_name(key)), SERVE_PROMPT_FONT_SIZE),
+        None => {
+            let remaining = ball_spawn_timer.0.duration().as_secs_f32() - ball_spawn_timer.0.elapsed_secs();
+            (format!("{}", remaining.ceil().max(1.)), COUNTDOWN_FONT_SIZE)
+        },
+    };
+
+    if let Ok((mut text, mut scaled_text)) = countdown_query.get_single_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+            text.sections[0].style.font_size = font_size * ui_scale.value();
+            scaled_text.0 = font_size;
+        }
+        return;
+    }
+
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                value,
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: font_size * ui_scale.value(),
+                    color: Color::rgba(1., 1., 1., 0.6),
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                },
+            ),
+            ..default()
+        })
+        .insert(Countdown)
+        .insert(ScaledText(font_size))
+        .insert(ThemedText)
+        .insert(Gameplay);
+}
+
+
+/// Announce a let above the serve countdown for a moment after a rally is called off
+pub fn let_indicator(
+    time: Res<Time>,
+    mut rally_lets: EventReader<RallyLet>,
+    ui_scale: Res<UiScale>,
+    mut indicator_query: Query<(Entity, &mut LetIndicator)>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for (indicator, mut timer) in indicator_query.iter_mut() {
+        if timer.0.tick(time.delta()).finished() {
+            commands.entity(indicator).despawn();
+        }
+    }
+
+    if rally_lets.iter().count() == 0 {
+        return;
+    }
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                "Let",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: LET_FONT_SIZE * ui_scale.value(),
+                    color: Color::rgba(1., 1., 1., 0.8),
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                },
+            ),
+            transform: Transform::from_xyz(0., COUNTDOWN_FONT_SIZE, 0.),
+            ..default()
+        })
+        .insert(LetIndicator(Timer::from_seconds(LET_DURATION, false)))
+        .insert(ScaledText(LET_FONT_SIZE))
+        .insert(ThemedText)
+        .insert(Gameplay);
+}
+
+
+/// Cheer a comeback goal with a sound and a banner above the serve countdown for a moment
+#[allow(clippy::too_many_arguments)]
+pub fn comeback_cue(
+    time: Res<Time>,
+    mut comebacks: EventReader<Comeback>,
+    ui_scale: Res<UiScale>,
+    audio: Res<Audio>,
+    comeback_sound: Res<ComebackSound>,
+    audio_settings: Res<AudioSettings>,
+    mut banner_query: Query<(Entity, &mut ComebackBanner)>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for (banner, mut timer) in banner_query.iter_mut() {
+        if timer.0.tick(time.delta()).finished() {
+            commands.entity(banner).despawn();
+        }
+    }
+
+    if comebacks.iter().count() == 0 {
+        return;
+    }
+    if !audio_settings.muted {
+        audio.play_with_settings(
+            comeback_sound.0.clone(),
+            PlaybackSettings::ONCE.with_volume(audio_settings.volume(COMEBACK_VOLUME)),
+        );
+    }
+    // A fresh banner replaces any still showing
+    for (banner, _) in banner_query.iter() {
+        commands.entity(banner).despawn();
+    }
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                "Comeback!",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: COMEBACK_FONT_SIZE * ui_scale.value(),
+                    color: COMEBACK_COLOR,
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                },
+            ),
+            transform: Transform::from_xyz(0., COUNTDOWN_FONT_SIZE, 0.),
+            ..default()
+        })
+        .insert(ComebackBanner(Timer::from_seconds(COMEBACK_DURATION, false)))
+        .insert(ScaledText(COMEBACK_FONT_SIZE))
+        .insert(Gameplay);
+}
+
+
+/// Spawn a ball at the given position, in its own color if it has one
+fn spawn_ball(
+    commands: &mut Commands,
+    config: &GameConfig,
+    position: Vec2,
+    velocity: Vec2,
+    serve: Serve,
+    ball_color: Option<Color>,
+) {
+    let mut ball = commands.spawn();
+    ball.insert(Ball)
+        .insert(Gameplay)
+        .insert(serve)
+        .insert(Velocity(velocity))
+        .insert(StuckDetector { anchor: position, ..default() })
+        .insert_bundle(SpriteBundle {
+            transform: Transform {
+                translation: position.extend(0.),
+                ..default()
+            },
+            sprite: Sprite {
+                color: ball_color.unwrap_or(Color::WHITE),
+                custom_size: Some(config.ball_size),
+                ..default()
+            },
+            ..default()
+        });
+    if let Some(color) = ball_color {
+        ball.insert(BallColor(color));
+    }
+}
+
+
+/// Debug helper to step through the physics while paused, a tick for each press of Period
+#[cfg(debug_assertions)]
+pub fn step_once(keyboard_input: Res<Input<KeyCode>>, mut step_once: ResMut<StepOnce>) {
+    if keyboard_input.just_pressed(KeyCode::Period) {
+        step_once.0 = true;
+    }
+}
+
+
+/// Run criteria for the practice helpers: while playing, in practice mode or any debug build
+pub fn practice_helpers_enabled(app_state: Res<State<AppState>>, practice_mode: Res<PracticeMode>) -> ShouldRun {
+    if *app_state.current() == AppState::Playing && (practice_mode.0 || cfg!(debug_assertions)) {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+
+/// Practice helper to instantly reset the current rally (Backspace), leaving the score untouched
+///  - Despawn the ball, so the same serve is repeated
+///  - Recenter both paddles
+///  - Restart the serve timer
+#[allow(clippy::type_complexity)]
+pub fn reset_rally(
+    keyboard_input: Res<Input<KeyCode>>,
+    ball_query: Query<Entity, With<Ball>>,
+    mut player_query: Query<(&mut Transform, &mut Velocity), (With<Player>, Without<Opponent>)>,
+    mut opponent_query: Query<(&mut Transform, &mut Velocity), With<Opponent>>,
+    mut ball_spawn_timer: ResMut<BallSpawnTimer>,
+    mut player_turn: ResMut<PlayerTurn>,
+    mut commands: Commands,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Back) {
+        return;
+    }
+
+    if !ball_query.is_empty() {
+        // Undo the turn switch from when the rally was served
+        player_turn.0 = !player_turn.0;
+    }
+    for ball in ball_query.iter() {
+        commands.entity(ball).despawn();
+    }
+
+    let (mut player_transform, mut player_velocity) = player_query.single_mut();
+    player_transform.translation.y = 0.;
+    player_velocity.0 = Vec2::ZERO;
+    let (mut opponent_transform, mut opponent_velocity) = opponent_query.single_mut();
+    opponent_transform.translation.y = 0.;
+    opponent_velocity.0 = Vec2::ZERO;
+
+    ball_spawn_timer.0.reset();
+}
+
+
+/// Predict the ball's Y position when it reaches `target_x`, following its bounces off the top and
+/// bottom walls. None if it isn't heading toward `target_x`.
+fn predict_ball_y(position: Vec2, velocity: Vec2, target_x: f32, arena: &Arena, config: &GameConfig) -> Option<f32> {
+    let time = (target_x - position.x) / velocity.x;
+    if !time.is_finite() || time < 0. {
+        return None;
+    }
+    let unbounded_y = position.y + velocity.y * time;
+
+    // Each wall bounce mirrors the path, so fold the unbounded Y back into the space between walls
+    let limit = (arena.height - config.ball_size.y) * 0.5;
+    let folded = (unbounded_y + limit).rem_euclid(4. * limit);
+    let folded = if folded > 2. * limit { 4. * limit - folded } else { folded };
+    Some(folded - limit)
+}
+
+
+// Tuning for the AI, whichever paddle it's driving
+#[derive(SystemParam)]
+pub struct AiControl<'w, 's> {
+    pub game_speed: Res<'w, GameSpeed>,
+    pub rest_bias: Res<'w, AiRestBias>,
+    pub smoothing: Res<'w, AiSmoothing>,
+    pub handicap: ResMut<'w, AiHandicap>,
+    pub difficulty: Res<'w, Difficulty>,
+    pub endless: Res<'w, Endless>,
+    pub config: Res<'w, GameConfig>,
+    pub arena: Res<'w, Arena>,
+    #[system_param(ignore)]
+    pub marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> AiControl<'w, 's> {
+    /// Very basic AI, returning the Y it's heading for and the paddle's new Y velocity
+    ///  - If ball does not exist or is moving away from the paddle, then ease back to its rest position
+    ///  - If ball is moving toward the paddle, then set Y-velocity based on distance to where the ball
+    ///    will arrive (or just to the ball on easy)
+    ///  - It only sees the ball as it was a short reaction time ago, one sample per tick
+    ///  - Tracking is optionally handicapped at the start of each rally
+    ///  - Tracking gain, top speed, reaction time and reaction lag come from the selected difficulty
+    ///  - Velocity is optionally smoothed toward that target over several ticks
+    ///  - Velocity changes are limited by paddle acceleration
+    pub fn paddle_velocity(
+        &mut self,
+        ball_query: &Query<(&Transform, &Velocity), With<Ball>>,
+        paddle_transform: &Transform,
+        velocity: f32,
+        speed_factor: f32,
+        observations: &mut VecDeque<Option<(Vec2, Vec2)>>,
+    ) -> (f32, f32) {
+        let (config, arena, difficulty) = (&*self.config, &*self.arena, *self.difficulty);
+        // Which way the paddle faces into the arena: -1 on the right (the opponent), 1 on the left
+        let facing = -paddle_transform.translation.x.signum();
+
+        // Ramp from the handicapped gain up to full over the start of each rally
+        self.handicap.ramp.tick(Duration::from_secs_f32(TIME_STEP));
+        let initial_factor = self.handicap.initial_factor;
+        let tracking_factor = initial_factor + (1. - initial_factor) * self.handicap.ramp.percent();
+
+        // With several balls in play, track whichever approaching ball is closest
+        let ball_approaching = ball_query
+            .iter()
+            .filter(|(_, ball_velocity)| ball_velocity.0.x * facing < 0.0)
+            .max_by(|(a, _), (b, _)| (a.translation.x * -facing).total_cmp(&(b.translation.x * -facing)))
+            .map(|(ball_transform, ball_velocity)| (ball_transform.translation.truncate(), ball_velocity.0));
+
+        // Act on the oldest observation within the reaction time
+        let boost = self.endless.ai_boost();
+        let delay_ticks = (difficulty.reaction_time() / boost / TIME_STEP).round() as usize;
+        observations.push_back(ball_approaching);
+        while observations.len() > delay_ticks + 1 {
+            observations.pop_front();
+        }
+        let ball_seen = observations.front().copied().flatten();
+
+        let (target_y, gain) = if let Some((ball_position, ball_velocity)) = ball_seen {
+            let intercept_x = paddle_transform.translation.x + facing * (config.paddle_size.x + config.ball_size.x) * 0.5;
+            let target_y = if difficulty.predicts_ball() {
+                predict_ball_y(ball_position, ball_velocity, intercept_x, arena, config).unwrap_or(ball_position.y)
+            } else {
+                ball_position.y
+            };
+            (target_y, difficulty.tracking_gain() * tracking_factor * boost)
+        } else {
+            (self.rest_bias.0, config.ai_rest_tracking)
+        };
+        let target_velocity = (target_y - paddle_transform.translation.y) * gain * self.game_speed.0;
+
+        let max_speed = difficulty.max_speed() * self.game_speed.0 * speed_factor * boost;
+        let target_velocity = target_velocity.clamp(-max_speed, max_speed);
+
+        // Ease toward the target rather than snapping to it
+        let response = 1. - self.smoothing.0.max(difficulty.reaction_lag()).clamp(0., 0.99);
+        let eased_velocity = velocity + (target_velocity - velocity) * response;
+        // Paddles have momentum, so can only change speed so quickly
+        (target_y, accelerate_paddle(config, velocity, eased_velocity))
+    }
+}
+
+
+/// Update scoreboard text based on current score, triggering an animation on any score that changed
+#[allow(clippy::type_complexity)]
+pub fn update_scoreboard(
+    scoreboard: Res<Scoreboard>,
+    mut player_query: Query<(&mut Text, &mut ScoreAnimation), With<PlayerScoreText>>,
+    mut opponent_query: Query<(&mut Text, &mut ScoreAnimation), (With<OpponentScoreText>, Without<PlayerScoreText>)>,
+) {
+    // Avoid re-layout of the text unless a score actually changed
+    if !scoreboard.is_changed() {
+        return;
+    }
+
+    let (text, animation) = player_query.single_mut();
+    set_score(text, animation, scoreboard.player);
+    let (text, animation) = opponent_query.single_mut();
+    set_score(text, animation, scoreboard.opponent);
+}
+
+
+/// Show a score, popping it if it changed
+fn set_score(mut text: Mut<Text>, mut animation: Mut<ScoreAnimation>, score: u16) {
+    let score = format!("{}", score);
+    if text.sections[0].value != score {
+        text.sections[0].value = score;
+        animation.0.reset();
+    }
+}
+
+
+/// Pause or resume the game with Escape or the pause key (P by default)
+///
+/// Paused is pushed on top of Playing, so gameplay entities stay alive (frozen) rather than being
+/// torn down, and resume exactly where they left off.
+pub fn toggle_pause(
+    keyboard_input: Res<Input<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    disconnect_pause: Res<DisconnectPause>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    // Paused for a lost gamepad, which only reconnecting it (or switching input mode) resumes from
+    if !keyboard_input.any_just_pressed([KeyCode::Escape, key_bindings.pause]) || disconnect_pause.waiting {
+        return;
+    }
+
+    // Ignore the press if another transition (e.g. game over) is already queued
+    let _ = match app_state.current() {
+        AppState::Playing => app_state.push(AppState::Paused),
+        AppState::Paused => app_state.pop(),
+        _ => return,
+    };
+}
+
+
+/// Pause the game when the window loses focus, so the player doesn't concede while away
+///
+/// Regaining focus doesn't resume the game, the player does that when they're ready.
+pub fn pause_on_focus_loss(
+    mut focus_events: EventReader<WindowFocused>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    let lost_focus = focus_events.iter().any(|event| event.id.is_primary() && !event.focused);
+    if lost_focus && *app_state.current() == AppState::Playing {
+        let _ = app_state.push(AppState::Paused);
+    }
+}
+
+
+/// Dim the screen and show that the game is paused (or why, if the gamepad disconnected)
+pub fn spawn_pause_overlay(
+    asset_server: Res<AssetServer>,
+    ui_scale: Res<UiScale>,
+    disconnect_pause: Res<DisconnectPause>,
+    mut commands: Commands,
+) {
+    let (message, font_size) = if disconnect_pause.waiting {
+        ("Controller disconnected — reconnect to continue", STATS_FONT_SIZE)
+    } else {
+        ("PAUSED", HEADING_FONT_SIZE)
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: PAUSE_OVERLAY_COLOR.into(),
+            ..default()
+        })
+        .insert(PauseUi)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    message,
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: font_size * ui_scale.value(),
+                        color: Color::WHITE,
+                    },
+                    default(),
+                ),
+                ..default()
+            })
+                .insert(ScaledText(font_size))
+                .insert(ThemedText);
+        });
+}
+
+
+/// Run down the clock of a timed match, and count up the time survived in endless mode. Like the
+/// rest of the match, they stop while paused
+pub fn tick_match_clock(
+    mut timed_match: ResMut<TimedMatch>,
+    mut endless: ResMut<Endless>,
+    mut match_stats: ResMut<MatchStats>,
+) {
+    if timed_match.enabled {
+        timed_match.remaining.tick(Duration::from_secs_f32(TIME_STEP));
+    }
+    if endless.enabled {
+        endless.survived += TIME_STEP;
+    }
+    match_stats.duration += TIME_STEP;
+}
+
+
+/// Whether a side on `score` has won the match against one on `other_score`
+fn has_won(score: u16, other_score: u16, winning_score: &WinningScore, win_by_two: &WinByTwo) -> bool {
+    score >= winning_score.0 && (!win_by_two.0 || score >= other_score + 2)
+}
+
+
+/// Whether the next goal could win the match, for a side that hasn't won already (in endless mode,
+/// whether the player is down to their last life)
+fn is_match_point(
+    scoreboard: &Scoreboard,
+    winning_score: &WinningScore,
+    win_by_two: &WinByTwo,
+    endless: &Endless,
+) -> bool {
+    if endless.enabled {
+        return endless.lives_left(scoreboard) == 1;
+    }
+    let leader = scoreboard.player.max(scoreboard.opponent);
+    let trailer = scoreboard.player.min(scoreboard.opponent);
+    !has_won(leader, trailer, winning_score, win_by_two) && has_won(leader + 1, trailer, winning_score, win_by_two)
+}
+
+
+/// Note whether the match is on match point, clearing it once that lapses (e.g. the trailing side
+/// pulls level playing win by two) or the match is over
+pub fn detect_match_point(
+    scoreboard: Res<Scoreboard>,
+    winning_score: Res<WinningScore>,
+    win_by_two: Res<WinByTwo>,
+    endless: Res<Endless>,
+    app_state: Res<State<AppState>>,
+    mut match_point: ResMut<MatchPoint>,
+) {
+    let in_match = matches!(app_state.current(), AppState::Playing | AppState::Paused);
+    let now_match_point = in_match && is_match_point(&scoreboard, &winning_score, &win_by_two, &endless);
+    // Only touched when it flips, so the presentation can react to the change
+    if match_point.0 != now_match_point {
+        match_point.0 = now_match_point;
+    }
+}
+
+
+/// End the match once either side reaches the winning score (leading by two, if that rule is on),
+/// or is ahead when a timed match runs out of time, counting it toward the series
+///  - If a side has now won the series, the game is over
+///  - Otherwise the score (along with the clock and stats) is reset for the next match
+///
+/// In endless mode there's no winning score, the game is over once the player runs out of lives.
+#[allow(clippy::too_many_arguments)]
+pub fn check_game_over(
+    mut scoreboard: ResMut<Scoreboard>,
+    winning_score: Res<WinningScore>,
+    win_by_two: Res<WinByTwo>,
+    endless: Res<Endless>,
+    mut timed_match: ResMut<TimedMatch>,
+    mut series: ResMut<Series>,
+    mut match_stats: ResMut<MatchStats>,
+    mut app_state: ResMut<State<AppState>>,
+    mut match_ended: EventWriter<MatchEnded>,
+) {
+    let time_up = timed_match.enabled && timed_match.remaining.finished();
+    let player_won = if endless.enabled {
+        if endless.lives_left(&scoreboard) > 0 {
+            return;
+        }
+        false
+    } else if has_won(scoreboard.player, scoreboard.opponent, &winning_score, &win_by_two) {
+        true
+    } else if has_won(scoreboard.opponent, scoreboard.player, &winning_score, &win_by_two) {
+        false
+    } else if time_up && scoreboard.player != scoreboard.opponent {
+        scoreboard.player > scoreboard.opponent
+    } else {
+        // Still playing, or in sudden death until the tie is broken
+        return;
+    };
+
+    if player_won {
+        series.player_matches += 1;
+    } else {
+        series.opponent_matches += 1;
+    }
+    match_ended.send(MatchEnded(scoreboard.clone(), match_stats.clone()));
+    if endless.enabled || series.player_matches.max(series.opponent_matches) >= series.matches_to_win() {
+        // If another transition (e.g. pausing) is already queued, the game ends once it's back
+        let _ = app_state.set(AppState::GameOver);
+    } else {
+        *scoreboard = Scoreboard { player: 0, opponent: 0 };
+        timed_match.remaining.reset();
+        *match_stats = MatchStats::default();
+    }
+}
+
+
+/// Show the time left in a timed match, or that it's gone to sudden death (or in endless mode, the
+/// time survived and lives left)
+pub fn update_match_clock_text(
+    timed_match: Res<TimedMatch>,
+    endless: Res<Endless>,
+    scoreboard: Res<Scoreboard>,
+    mut clock_query: Query<&mut Text, With<MatchClockText>>,
+) {
+    let value = if endless.enabled {
+        format!("{}    Lives: {}", clock_time(endless.survived), endless.lives_left(&scoreboard))
+    } else if !timed_match.enabled {
+        String::new()
+    } else if timed_match.remaining.finished() {
+        "Sudden Death".to_string()
+    } else {
+        let remaining = timed_match.remaining.duration() - timed_match.remaining.elapsed();
+        clock_time(remaining.as_secs_f32().ceil())
+    };
+    for mut text in clock_query.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+
+/// Seconds as m:ss, rounded down
+fn clock_time(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+
+/// Show the number of paddle hits in the current rally, dropping back to 0 as soon as a goal ends it
+pub fn update_rally_text(
+    rally_count: Res<RallyCount>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut rally_query: Query<&mut Text, With<RallyText>>,
+) {
+    let goal_scored = collision_events.iter().any(|event| matches!(event, CollisionEvent::Goal));
+    if !rally_count.is_changed() && !goal_scored {
+        return;
+    }
+
+    let hits = if goal_scored { 0 } else { rally_count.0 };
+    for mut text in rally_query.iter_mut() {
+        text.sections[0].value = format!("Rally: {}", hits);
+    }
+}
+
+
+/// Show the series score under the scoreboard, when playing a series of more than one match
+pub fn update_series_text(series: Res<Series>, mut series_query: Query<&mut Text, With<SeriesText>>) {
+    for mut text in series_query.iter_mut() {
+        if series.is_changed() || text.is_added() {
+            text.sections[0].value = if series.best_of > 1 {
+                format!("Series {} - {} (best of {})", series.player_matches, series.opponent_matches, series.best_of)
+            } else {
+                String::new()
+            };
+        }
+    }
+}
+
+
+/// Announce the winner, final score and match stats, with buttons to play again or go back to the
+/// title screen
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_game_over_text(
+    scoreboard: Res<Scoreboard>,
+    series: Res<Series>,
+    match_stats: Res<MatchStats>,
+    leaderboard: Res<RallyLeaderboard>,
+    session_stats: Res<SessionStats>,
+    endless: Res<Endless>,
+    asset_server: Res<AssetServer>,
+    ui_scale: Res<UiScale>,
+    mut focus: ResMut<MenuFocus>,
+    mut commands: Commands,
+) {
+    let winner = if scoreboard.player > scoreboard.opponent { "Player" } else { "Opponent" };
+    let (headline, final_score) = if endless.enabled {
+        (
+            "Game Over".to_string(),
+            format!("Survived {}, scoring {}", clock_time(endless.survived), scoreboard.player),
+        )
+    } else if series.best_of > 1 {
+        (
+            format!("{} Wins the Series!", winner),
+            format!(
+                "{} - {}  (series {} - {})",
+                scoreboard.player, scoreboard.opponent, series.player_matches, series.opponent_matches,
+            ),
+        )
+    } else {
+        (format!("{} Wins!", winner), format!("{} - {}", scoreboard.player, scoreboard.opponent))
+    };
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    focus.0 = 0;
+
+    commands
+        .spawn_bundle(menu_screen_node())
+        .insert(GameOverText)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    headline,
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: HEADING_FONT_SIZE * ui_scale.value(),
+                        color: Color::WHITE,
+                    },
+                    default(),
+                ),
+                ..default()
+            })
+                .insert(ScaledText(HEADING_FONT_SIZE))
+                .insert(ThemedText);
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect {
+                        bottom: Val::Px(16.),
+                        ..default()
+                    },
+                    ..default()
+                },
+                text: Text::with_section(
+                    final_score,
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: FINAL_SCORE_FONT_SIZE * ui_scale.value(),
+                        color: Color::rgb(0.65, 0.65, 0.65),
+                    },
+                    default(),
+                ),
+                ..default()
+            })
+                .insert(ScaledText(FINAL_SCORE_FONT_SIZE));
+            let mut stats = vec![
+                format!("Rallies: {}    Longest rally: {}", match_stats.rallies, match_stats.longest_rally),
+                format!(
+                    "Wall bounces: {}    Top ball speed: {:.0}",
+                    match_stats.wall_bounces, match_stats.fastest_ball_speed,
+                ),
+            ];
+            // Only worth showing once the session has gone on past this match
+            if session_stats.matches > 1 {
+                stats.push(format!(
+                    "Session: {} matches    Rallies: {}    Longest rally: {}    Played: {}",
+                    session_stats.matches,
+                    session_stats.rallies,
+                    session_stats.longest_rally,
+                    clock_time(session_stats.duration),
+                ));
+            }
+            if !leaderboard.rallies.is_empty() {
+                let top_rallies: Vec<String> = leaderboard.rallies
+                    .iter()
+                    .take(RALLY_LEADERBOARD_SHOWN)
+                    .map(|rally| format!("{} ({})", rally.count, rally.date))
+                    .collect();
+                stats.push(format!("Top rallies: {}", top_rallies.join("    ")));
+            }
+            let last_line = stats.len() - 1;
+            for (line, stat) in stats.into_iter().enumerate() {
+                parent.spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect {
+                            // Space the buttons off below the last line
+                            bottom: Val::Px(if line == last_line { 32. } else { 4. }),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    text: Text::with_section(
+                        stat,
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: STATS_FONT_SIZE * ui_scale.value(),
+                            color: Color::rgb(0.65, 0.65, 0.65),
+                        },
+                        default(),
+                    ),
+                    ..default()
+                })
+                    .insert(ScaledText(STATS_FONT_SIZE));
+            }
+            for (index, (label, action)) in [
+                ("Rematch", MenuAction::Rematch),
+                ("Main Menu", MenuAction::MainMenu),
+            ].into_iter().enumerate() {
+                spawn_menu_button(parent, font.clone(), &ui_scale, label.to_string(), MenuButton { action, index });
+            }
+        });
+}
+
+
+/// Reset the match, then play it again or go back to the title screen, as chosen on the game over
+/// screen. The session stats carry over a rematch (if enabled), but not going back to the title.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_game_over_actions(
+    mut menu_actions: EventReader<MenuAction>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut series: ResMut<Series>,
+    mut timed_match: ResMut<TimedMatch>,
+    mut match_stats: ResMut<MatchStats>,
+    mut player_turn: ResMut<PlayerTurn>,
+    mut ball_spawn_timer: ResMut<BallSpawnTimer>,
+    mut endless: ResMut<Endless>,
+    mut session_stats: ResMut<SessionStats>,
+    carry_session_stats: Res<CarrySessionStats>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    for action in menu_actions.iter() {
+        let next_state = match action {
+            MenuAction::Rematch => AppState::Playing,
+            MenuAction::MainMenu => AppState::Menu,
+            _ => continue,
+        };
+        if next_state == AppState::Menu || !carry_session_stats.0 {
+            *session_stats = SessionStats::default();
+        }
+        *scoreboard = Scoreboard { player: 0, opponent: 0 };
+        series.player_matches = 0;
+        series.opponent_matches = 0;
+        timed_match.remaining.reset();
+        *match_stats = MatchStats::default();
+        endless.survived = 0.;
+        player_turn.0 = true;
+        ball_spawn_timer.0.reset();
+        // Ignore the choice if another transition is already queued
+        let _ = app_state.set(next_state);
+    }
+}
+
+
+/// Despawn all entities (and their children) with the given marker component
+pub fn despawn_with<T: Component>(query: Query<Entity, With<T>>, mut commands: Commands) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+
+/// Resize all UI text, menu buttons, and the scoreboard margins, whenever the UI scale setting
+/// changes, and keep the scores either side of the net as the arena is resized
+#[allow(clippy::type_complexity)]
+pub fn apply_ui_scale(
+    ui_scale: Res<UiScale>,
+    arena: Res<Arena>,
+    mut text_query: Query<(&mut Text, &ScaledText)>,
+    mut score_query: Query<(&mut Style, Option<&PlayerScoreText>), (With<ScoreText>, Without<MenuButton>)>,
+    mut button_query: Query<&mut Style, With<MenuButton>>,
+) {
+    if !ui_scale.is_changed() && !arena.is_changed() {
+        return;
+    }
+
+    for (mut text, scaled_text) in text_query.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.font_size = scaled_text.0 * ui_scale.value();
+        }
+    }
+    // Scoreboard only exists while playing
+    for (mut score_style, player) in score_query.iter_mut() {
+        score_style.margin = score_margin(&arena, &ui_scale, player.is_some());
+    }
+    for mut button_style in button_query.iter_mut() {
+        *button_style = menu_button_style(&ui_scale);
+    }
+}
+
+
+/// Margin placing a score just below the top edge, out from the net on its side
+fn score_margin(arena: &Arena, ui_scale: &UiScale, is_player: bool) -> Rect<Val> {
+    let from_middle = Val::Px(arena.width * 0.5 + SCORE_NET_GAP * ui_scale.value());
+    Rect {
+        top: Val::Percent(7. * ui_scale.value()),
+        left: if is_player { Val::Undefined } else { from_middle },
+        right: if is_player { from_middle } else { Val::Undefined },
+        ..default()
+    }
+}
+
+
+/// Scale-pop and color-flash recently changed scores, easing back to normal
+pub fn animate_score(
+    time: Res<Time>,
+    ui_scale: Res<UiScale>,
+    theme: Res<Theme>,
+    reduce_motion: Res<ReduceMotion>,
+    mut score_query: Query<(&mut Text, &mut ScoreAnimation), With<ScoreText>>,
+) {
+    for (mut score_text, mut animation) in score_query.iter_mut() {
+        let timer = &mut animation.0;
+        if timer.finished() {
+            continue;
+        }
+        timer.tick(time.delta());
+
+        // Ease out: strongest right after the change, settling back to normal
+        let strength = timer.percent_left().powi(2);
+        let style = &mut score_text.sections[0].style;
+        // With reduced motion the score only flashes, without popping
+        let pop = if reduce_motion.0 { 0. } else { SCORE_POP_SCALE * strength };
+        style.font_size = SCORE_FONT_SIZE * ui_scale.value() * (1. + pop);
+        style.color = lerp_color(theme.text, SCORE_FLASH_COLOR, strength);
+    }
+}
+
+
+/// Leave a ghost of each ball behind it every frame, if the ball trail is enabled (and motion isn't
+/// reduced)
+pub fn spawn_ball_trail(
+    ball_trail: Res<BallTrail>,
+    reduce_motion: Res<ReduceMotion>,
+    ball_query: Query<(&Transform, &Sprite), With<Ball>>,
+    mut commands: Commands,
+) {
+    if !ball_trail.0 || reduce_motion.0 {
+        return;
+    }
+
+    for (transform, sprite) in ball_query.iter() {
+        let mut color = sprite.color;
+        color.set_a(TRAIL_ALPHA);
+        commands
+            .spawn()
+            .insert(TrailFade(Timer::from_seconds(TRAIL_FADE_DURATION, false)))
+            .insert(Gameplay)
+            .insert_bundle(SpriteBundle {
+                transform: Transform {
+                    // Just behind the ball
+                    translation: transform.translation - Vec3::Z * 0.1,
+                    ..default()
+                },
+                sprite: Sprite {
+                    color,
+                    custom_size: sprite.custom_size,
+                    ..default()
+                },
+                ..default()
+            });
+    }
+}
+
+
+/// Shrink and fade out ball trail ghosts, despawning them once gone
+pub fn fade_ball_trail(
+    time: Res<Time>,
+    mut trail_query: Query<(Entity, &mut Transform, &mut Sprite, &mut TrailFade)>,
+    mut commands: Commands,
+) {
+    for (ghost, mut transform, mut sprite, mut fade) in trail_query.iter_mut() {
+        if fade.0.tick(time.delta()).finished() {
+            commands.entity(ghost).despawn();
+            continue;
+        }
+        let remaining = fade.0.percent_left();
+        transform.scale = Vec3::splat(remaining);
+        sprite.color.set_a(TRAIL_ALPHA * remaining);
+    }
+}
+
+
+/// In classic mode, turn off everything original Pong didn't have: rally speed-up, spin, ball trails,
+/// random serves, power-ups, multi-ball, the sticky paddle and the magnet, bouncing the ball off the
+/// paddles at an angle set purely by where it hit
+#[allow(clippy::too_many_arguments)]
+pub fn apply_classic_mode(
+    classic_mode: Res<ClassicMode>,
+    mut config: ResMut<GameConfig>,
+    mut bounce_mode: ResMut<BounceMode>,
+    mut ball_trail: ResMut<BallTrail>,
+    mut random_serve: ResMut<RandomServe>,
+    mut power_ups: ResMut<PowerUps>,
+    mut multi_ball: ResMut<MultiBall>,
+    mut sticky_paddle: ResMut<StickyPaddle>,
+    mut magnet_mode: ResMut<MagnetMode>,
+) {
+    if !classic_mode.0 {
+        return;
+    }
+    config.rally_speed_ramp = 1.;
+    config.paddle_spin_factor = 0.;
+    *bounce_mode = BounceMode::Positional;
+    ball_trail.0 = false;
+    random_serve.enabled = false;
+    power_ups.enabled = false;
+    multi_ball.enabled = false;
+    sticky_paddle.0 = false;
+    magnet_mode.0 = false;
+}
+
+
+/// Throw a burst of sparks out from where the ball hits a paddle (unless motion is reduced, or in
+/// classic mode), leaving out any that would go over the cap on how many can be around at once
+pub fn spawn_paddle_particles(
+    mut collision_events: EventReader<CollisionEvent>,
+    reduce_motion: Res<ReduceMotion>,
+    classic_mode: Res<ClassicMode>,
+    theme: Res<Theme>,
+    particle_query: Query<(), With<Particle>>,
+    mut commands: Commands,
+) {
+    // Purely cosmetic, so kept off the game's seeded randomness to leave the match reproducible
+    let mut rng = rand::thread_rng();
+    let mut particle_count = particle_query.iter().count();
+
+    for event in collision_events.iter() {
+        let contact = match event {
+            CollisionEvent::PaddleBounce(contact) if !reduce_motion.0 && !classic_mode.0 => *contact,
+            _ => continue,
+        };
+        // Away from the paddle, back toward the middle of the arena
+        let outward = if contact.x > 0. { std::f32::consts::PI } else { 0. };
+
+        let burst = PARTICLES_PER_HIT.min(MAX_PARTICLES.saturating_sub(particle_count));
+        particle_count += burst;
+        for _ in 0..burst {
+            let angle = outward + rng.gen_range(-PARTICLE_SPREAD..=PARTICLE_SPREAD);
+            let speed = rng.gen_range(PARTICLE_MIN_SPEED..=PARTICLE_MAX_SPEED);
+            commands
+                .spawn()
+                .insert(Particle {
+                    timer: Timer::from_seconds(PARTICLE_LIFETIME, false),
+                    velocity: Vec2::new(angle.cos(), angle.sin()) * speed,
+                })
+                .insert(Gameplay)
+                .insert_bundle(SpriteBundle {
+                    transform: Transform {
+                        // Behind the ball and its trail
+                        translation: contact.extend(-0.2),
+                        ..default()
+                    },
+                    sprite: Sprite {
+                        color: theme.ball,
+                        custom_size: Some(PARTICLE_SIZE),
+                        ..default()
+                    },
+                    ..default()
+                });
+        }
+    }
+}
+
+
+/// Move sparks along, fading them out and despawning them once their time is up
+pub fn update_particles(
+    time: Res<Time>,
+    mut particle_query: Query<(Entity, &mut Transform, &mut Sprite, &mut Particle)>,
+    mut commands: Commands,
+) {
+    for (entity, mut transform, mut sprite, mut particle) in particle_query.iter_mut() {
+        if particle.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation += (particle.velocity * time.delta_seconds()).extend(0.);
+        sprite.color.set_a(particle.timer.percent_left());
+    }
+}
+
+
+/// Load saved settings, applying them to the resources they control
+#[allow(clippy::too_many_arguments)]
+pub fn load_settings(
+    mut settings: ResMut<Settings>,
+    mut audio_settings: ResMut<AudioSettings>,
+    mut difficulty: ResMut<Difficulty>,
+    mut input_mode: ResMut<InputMode>,
+    mut theme: ResMut<Theme>,
+    mut reduce_motion: ResMut<ReduceMotion>,
+    mut key_bindings: ResMut<KeyBindings>,
+    mut replay_buffer: ResMut<ReplayBuffer>,
+    mut serve_spawn: ResMut<ServeSpawn>,
+    mut input_assignments: ResMut<InputAssignments>,
+    mut ui_scale: ResMut<UiScale>,
+) {
+    *settings = load_data_file(SETTINGS_FILE);
+    audio_settings.master_volume = settings.master_volume;
+    audio_settings.muted = settings.muted;
+    audio_settings.music_enabled = settings.music_enabled;
+    *difficulty = settings.difficulty;
+    *input_mode = settings.input_mode;
+    *theme = Theme {
+        distinct_paddles: settings.distinct_paddles,
+        ..Theme::from_preset(settings.theme)
+    };
+    reduce_motion.0 = settings.reduce_motion;
+    *serve_spawn = if settings.corner_serves { ServeSpawn::Corners { top: true } } else { ServeSpawn::Center };
+    *input_assignments = InputAssignments { player: settings.player_input, opponent: settings.opponent_input };
+    *key_bindings = settings.key_bindings;
+    *replay_buffer = ReplayBuffer::new(settings.replay_seconds);
+    ui_scale.0 = settings.ui_scale;
+}
+
+
+/// Save settings whenever any of them change
+#[allow(clippy::too_many_arguments)]
+pub fn save_settings(
+    mut settings: ResMut<Settings>,
+    audio_settings: Res<AudioSettings>,
+    difficulty: Res<Difficulty>,
+    input_mode: Res<InputMode>,
+    theme: Res<Theme>,
+    reduce_motion: Res<ReduceMotion>,
+    key_bindings: Res<KeyBindings>,
+    replay_buffer: Res<ReplayBuffer>,
+    serve_spawn: Res<ServeSpawn>,
+    input_assignments: Res<InputAssignments>,
+    ui_scale: Res<UiScale>,
+) {
+    let current = Settings {
+        master_volume: audio_settings.master_volume,
+        muted: audio_settings.muted,
+        music_enabled: audio_settings.music_enabled,
+        difficulty: *difficulty,
+        input_mode: *input_mode,
+        theme: theme.preset,
+        distinct_paddles: theme.distinct_paddles,
+        reduce_motion: reduce_motion.0,
+        corner_serves: *serve_spawn != ServeSpawn::Center,
+        player_input: input_assignments.player,
+        opponent_input: input_assignments.opponent,
+        key_bindings: *key_bindings,
+        replay_seconds: replay_buffer.replay_seconds(),
+        ui_scale: ui_scale.0,
+    };
+    if current != *settings {
+        *settings = current;
+        save_data_file(SETTINGS_FILE, &*settings);
+    }
+}
+
+
+/// Keep the longest rally record up to date, saving it when a record-breaking rally ends in a goal
+pub fn record_longest_rally(
+    mut collision_events: EventReader<CollisionEvent>,
+    rally_count: Res<RallyCount>,
+    app_state: Res<State<AppState>>,
+    mut records: ResMut<Records>,
+) {
+    let goal_scored = collision_events.iter().any(|event| matches!(event, CollisionEvent::Goal));
+    // Demo rallies aren't the player's
+    let demo = *app_state.current() == AppState::Demo;
+    if goal_scored && !demo && rally_count.0 > records.longest_rally {
+        records.longest_rally = rally_count.0;
+        save_data_file(RECORDS_FILE, &*records);
+    }
+}
+
+
+/// Put each rally that ends in a goal on the rally leaderboard if it's long enough, saving it when
+/// one makes it on
+pub fn record_rally_leaderboard(
+    mut collision_events: EventReader<CollisionEvent>,
+    rally_count: Res<RallyCount>,
+    app_state: Res<State<AppState>>,
+    mut leaderboard: ResMut<RallyLeaderboard>,
+) {
+    let goal_scored = collision_events.iter().any(|event| matches!(event, CollisionEvent::Goal));
+    // Demo rallies aren't the player's
+    let demo = *app_state.current() == AppState::Demo;
+    if goal_scored && !demo && leaderboard.record(rally_count.0, &today()) {
+        save_data_file(RALLY_LEADERBOARD_FILE, &*leaderboard);
+    }
+}
+
+
+/// Append each finished match's stats to the match stats CSV, if enabled (demo matches aren't the
+/// player's)
+pub fn export_match_stats(
+    mut match_ended: EventReader<MatchEnded>,
+    export_match_stats: Res<ExportMatchStats>,
+    app_state: Res<State<AppState>>,
+    difficulty: Res<Difficulty>,
+    two_player: Res<TwoPlayer>,
+    classic_mode: Res<ClassicMode>,
+    endless: Res<Endless>,
+) {
+    for MatchEnded(scoreboard, match_stats) in match_ended.iter() {
+        if !export_match_stats.0 || *app_state.current() == AppState::Demo {
+            continue;
+        }
+        let mode = match (two_player.0, classic_mode.0, endless.enabled) {
+            (true, ..) => "Two Player",
+            (false, true, _) => "Classic",
+            (false, false, true) => "Endless",
+            (false, false, false) => "Single Player",
+        };
+        let row = match_stats.csv_row(&today(), mode, *difficulty, scoreboard);
+        append_data_csv_row(MATCH_STATS_FILE, MATCH_STATS_HEADER, &row);
+    }
+}
+
+
+/// Add each finished match to the session stats (demo matches never finish)
+pub fn record_session_stats(mut match_ended: EventReader<MatchEnded>, mut session_stats: ResMut<SessionStats>) {
+    for MatchEnded(_, match_stats) in match_ended.iter() {
+        session_stats.add_match(match_stats);
+    }
+}
+
+
+/// Save a new most-goals record when a match ends, whether or not it ends the series
+pub fn record_most_goals(mut match_ended: EventReader<MatchEnded>, mut records: ResMut<Records>) {
+    for MatchEnded(scoreboard, _) in match_ended.iter() {
+        if scoreboard.player > records.most_goals {
+            records.most_goals = scoreboard.player;
+            save_data_file(RECORDS_FILE, &*records);
+        }
+    }
+}
+
+
+/// Write the match's recorded input to the replay file when it ends
+pub fn save_replay(replay_mode: Res<ReplayMode>, replay: Res<Replay>) {
+    if *replay_mode == ReplayMode::Recording {
+        save_data_file(REPLAY_FILE, &*replay);
+    }
+}
+
+
+/// Create a timer that starts out already finished
+pub fn finished_timer(duration: f32) -> Timer {
+    let mut timer = Timer::from_seconds(duration, false);
+    timer.tick(timer.duration());
+    timer
+}
+
+
+/// Linearly interpolate between two colors
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    let from = from.as_rgba_f32();
+    let to = to.as_rgba_f32();
+    Color::rgba(
+        from[0] + (to[0] - from[0]) * t,
+        from[1] + (to[1] - from[1]) * t,
+        from[2] + (to[2] - from[2]) * t,
+        from[3] + (to[3] - from[3]) * t,
+    )
+}
+
+
+/// Play appropriate collision sounds in response to collision events
+pub fn play_sounds(
+    mut collision_events: EventReader<CollisionEvent>,
+    audio: Res<Audio>,
+    hit_sound: Res<HitSound>,
+    goal_sound: Res<GoalSound>,
+    wall_sound: Res<WallSound>,
+    audio_settings: Res<AudioSettings>,
+) {
+    for event in collision_events.iter() {
+        if audio_settings.muted {
+            continue;
+        }
+        let (sound, volume, speed) = match event {
+            CollisionEvent::WallBounce => (&wall_sound.0, WALL_VOLUME, WALL_SOUND_SPEED),
+            CollisionEvent::PaddleBounce(_) => (&hit_sound.0, HIT_VOLUME, 1.0),
+            CollisionEvent::Goal => (&goal_sound.0, GOAL_VOLUME, 1.0),
+        };
+        audio.play_with_settings(
+            sound.clone(),
+            PlaybackSettings::ONCE.with_volume(audio_settings.volume(volume)).with_speed(speed),
+        );
+    }
+}
+
+
+/// Play the serve charge-up sound as the countdown runs out (if enabled), cutting it off if the
+/// ball's served before it's finished
+pub fn play_serve_charge(
+    ball_spawn_timer: Res<BallSpawnTimer>,
+    mut serve_charge: ResMut<ServeCharge>,
+    audio: Res<Audio>,
+    charge_sound: Res<ServeChargeSound>,
+    audio_settings: Res<AudioSettings>,
+    audio_sinks: Res<Assets<AudioSink>>,
+) {
+    if ball_spawn_timer.0.finished() {
+        if let Some(sink) = serve_charge.sink.take().and_then(|sink| audio_sinks.get(sink)) {
+            sink.stop();
+        }
+        return;
+    }
+    if serve_charge.should_start(&ball_spawn_timer.0) && !audio_settings.muted {
+        let sink = audio.play_with_settings(
+            charge_sound.0.clone(),
+            PlaybackSettings::ONCE.with_volume(audio_settings.volume(SERVE_CHARGE_VOLUME)),
+        );
+        serve_charge.sink = Some(audio_sinks.get_handle(sink));
+    }
+}
+
+
+/// Switch between windowed and borderless fullscreen with F11
+pub fn toggle_fullscreen(keyboard_input: Res<Input<KeyCode>>, mut windows: ResMut<Windows>) {
+    if !keyboard_input.just_pressed(KeyCode::F11) {
+        return;
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        let mode = match window.mode() {
+            WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+            _ => WindowMode::Windowed,
+        };
+        window.set_mode(mode);
+    }
+}
+
+
+/// Zoom the camera so the whole arena stays in view, letterboxing any extra space
+///
+/// The arena normally follows the window size, so this only zooms out once the window is smaller
+/// than the minimum arena size.
+pub fn fit_camera_to_window(
+    mut resize_events: EventReader<WindowResized>,
+    arena: Res<Arena>,
+    mut camera_query: Query<&mut CameraZoom, With<MainCamera>>,
+) {
+    for event in resize_events.iter().filter(|event| event.id.is_primary()) {
+        let scale = (arena.width / event.width).max(arena.height / event.height);
+        for mut zoom in camera_query.iter_mut() {
+            zoom.fit = scale;
+        }
+    }
+}
+
+
+/// Punch the camera in slightly on paddle hits (unless motion is reduced, or in classic mode),
+/// zooming straight back out, on top of the zoom fitting the arena in the window
+///
+/// Only the rendered projection changes, the arena and everything in it stay where they are.
+pub fn zoom_camera(
+    time: Res<Time>,
+    mut collision_events: EventReader<CollisionEvent>,
+    reduce_motion: Res<ReduceMotion>,
+    classic_mode: Res<ClassicMode>,
+    mut camera_query: Query<(&mut OrthographicProjection, &mut CameraZoom), With<MainCamera>>,
+) {
+    let paddle_hit = collision_events.iter().any(|event| matches!(event, CollisionEvent::PaddleBounce(_)));
+
+    for (mut projection, mut zoom) in camera_query.iter_mut() {
+        if paddle_hit && !reduce_motion.0 && !classic_mode.0 {
+            zoom.punch.reset();
+        }
+        zoom.punch.tick(time.delta());
+
+        // In and back out again over the punch
+        let punch = if zoom.punch.finished() {
+            0.
+        } else {
+            CAMERA_PUNCH_ZOOM * (zoom.punch.percent() * std::f32::consts::PI).sin()
+        };
+        let scale = zoom.fit * (1. - punch);
+        if projection.scale != scale {
+            projection.scale = scale;
+        }
+    }
+}
+
+
+/// Ease the camera back to the center of the arena, and its zoom back to fitting the arena, over the
+/// serve countdown (if enabled), arriving exactly as the ball is served
+pub fn recenter_camera(
+    camera_recenter: Res<CameraRecenter>,
+    ball_spawn_timer: Res<BallSpawnTimer>,
+    mut camera_query: Query<(&mut Transform, &mut CameraZoom), With<MainCamera>>,
+    // How far into the countdown the camera was last eased
+    mut eased_until: Local<f32>,
+) {
+    let timer = &ball_spawn_timer.0;
+    let (elapsed, duration) = (timer.elapsed_secs(), timer.duration().as_secs_f32());
+    if elapsed < *eased_until {
+        // A new countdown
+        *eased_until = 0.;
+    }
+    // Nothing to do once the ball is in play, the camera was recentered as it was served
+    if !camera_recenter.0 || *eased_until >= duration {
+        return;
+    }
+
+    // Cover the same share of the way to center as of the countdown left, so it's there at the serve
+    let fraction = if timer.finished() {
+        1.
+    } else {
+        ((elapsed - *eased_until) / (duration - *eased_until)).clamp(0., 1.)
+    };
+    *eased_until = if timer.finished() { duration } else { elapsed };
+
+    for (mut transform, mut zoom) in camera_query.iter_mut() {
+        transform.translation.x *= 1. - fraction;
+        transform.translation.y *= 1. - fraction;
+        // Play the rest of any punch out over the same time, it ends back at the fitted zoom
+        let punch_left = zoom.punch.duration().saturating_sub(zoom.punch.elapsed());
+        zoom.punch.tick(punch_left.mul_f32(fraction));
+    }
+}
+
+
+/// Resize the arena to fill the window whenever it's resized (down to the minimum arena size, below
+/// which the camera zooms out instead)
+pub fn resize_arena(mut resize_events: EventReader<WindowResized>, mut arena: ResMut<Arena>) {
+    for event in resize_events.iter().filter(|event| event.id.is_primary()) {
+        let width = event.width.max(MIN_ARENA_SIZE);
+        let height = event.height.max(MIN_ARENA_SIZE);
+        if width != arena.width || height != arena.height {
+            arena.width = width;
+            arena.height = height;
+        }
+    }
+}
+
+
+/// Lay out everything on the field again whenever the arena is resized
+///  - Rebuild the net, travel zone marks, magnet and solid net
+///  - Move the paddles and stamina bars to the new edges, keeping paddles within their new bounds
+///  - Keep balls and power-ups inside the walls
+///  - Stretch the heatmap overlay over the new arena
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn fit_gameplay_to_arena(
+    arena: Res<Arena>,
+    config: Res<GameConfig>,
+    travel_fraction: Res<PlayerTravelFraction>,
+    magnet_mode: Res<MagnetMode>,
+    solid_net: Res<SolidNet>,
+    fixture_query: Query<Entity, With<ArenaFixture>>,
+    mut paddle_query: Query<(&mut Transform, Option<&Player>), Or<(With<Player>, With<Opponent>)>>,
+    mut bar_query: Query<&mut Transform, (With<StaminaBar>, Without<Player>, Without<Opponent>)>,
+    mut loose_query: Query<
+        &mut Transform,
+        (Or<(With<Ball>, With<PowerUp>, With<Target>)>, Without<StaminaBar>, Without<Player>, Without<Opponent>),
+    >,
+    mut cell_query: Query<
+        (&HeatmapCell, &mut Transform, &mut Sprite),
+        (Without<Ball>, Without<PowerUp>, Without<Target>, Without<StaminaBar>, Without<Player>, Without<Opponent>),
+    >,
+    mut commands: Commands,
+) {
+    // The field is laid out for the arena as it is when it's spawned
+    if !arena.is_changed() || arena.is_added() {
+        return;
+    }
+
+    if !fixture_query.is_empty() {
+        for fixture in fixture_query.iter() {
+            commands.entity(fixture).despawn();
+        }
+        spawn_net(&mut commands, &arena);
+        spawn_travel_zone_marks(&mut commands, &arena, travel_fraction.0);
+        if magnet_mode.0 {
+            spawn_magnet(&mut commands, &arena);
+        }
+        if solid_net.enabled {
+            spawn_solid_net(&mut commands, &arena, solid_net.gap);
+        }
+    }
+
+    let paddle_x = arena.width * 0.5 - 26.;
+    for (mut transform, player) in paddle_query.iter_mut() {
+        let (side, bound) = match player {
+            Some(_) => (-1., paddle_bound(&arena, &config, travel_fraction.0)),
+            None => (1., paddle_bound(&arena, &config, 1.0)),
+        };
+        transform.translation.x = side * paddle_x;
+        transform.translation.y = transform.translation.y.clamp(-bound, bound);
+    }
+    for mut transform in bar_query.iter_mut() {
+        transform.translation.x = transform.translation.x.signum() * paddle_x * 0.8;
+        transform.translation.y = -arena.height * 0.5 + 12.;
+    }
+
+    let half_size = Vec2::new(arena.width, arena.height - config.ball_size.y) * 0.5;
+    for mut transform in loose_query.iter_mut() {
+        let position = transform.translation.truncate().clamp(-half_size, half_size);
+        transform.translation = position.extend(transform.translation.z);
+    }
+
+    for (cell, mut transform, mut sprite) in cell_query.iter_mut() {
+        let (translation, cell_size) = heatmap_cell_layout(&arena, cell.0);
+        transform.translation = translation;
+        sprite.custom_size = Some(cell_size);
+    }
+}
+
+
+/// Show/hide the debug overlay with F3, or just its input latency readout with F4
+pub fn toggle_debug_overlay(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut debug_query: Query<(Entity, &mut DebugText)>,
+    asset_server: Res<AssetServer>,
+    ui_scale: Res<UiScale>,
+    mut input_latency: ResMut<InputLatency>,
+    mut commands: Commands,
+) {
+    let latency_only = if keyboard_input.just_pressed(KeyCode::F3) {
+        false
+    } else if keyboard_input.just_pressed(KeyCode::F4) {
+        true
+    } else {
+        return;
+    };
+
+    if let Ok((entity, mut debug_text)) = debug_query.get_single_mut() {
+        // The other key switches between the two, carrying on measuring
+        if debug_text.latency_only != latency_only {
+            debug_text.latency_only = latency_only;
+            return;
+        }
+        // Despawned rather than hidden, so it costs nothing while off
+        commands.entity(entity).despawn();
+        *input_latency = InputLatency::default();
+        return;
+    }
+    input_latency.measuring = true;
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(8.),
+                    left: Val::Px(8.),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: DEBUG_FONT_SIZE * ui_scale.value(),
+                    color: Color::rgb(0.4, 1.0, 0.4),
+                },
+                default(),
+            ),
+            ..default()
+        })
+        .insert(DebugText { latency_only })
+        .insert(ScaledText(DEBUG_FONT_SIZE));
+}
+
+
+/// Register the input latency diagnostic, shown in the debug overlay
+pub fn setup_input_latency_diagnostic(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(InputLatency::DIAGNOSTIC, "input_latency", 20).with_suffix("ms"));
+}
+
+
+/// Add the latest input latency measurement, if any, to its diagnostic
+pub fn record_input_latency(mut input_latency: ResMut<InputLatency>, mut diagnostics: ResMut<Diagnostics>) {
+    if let Some(latency) = input_latency.measured.take() {
+        diagnostics.add_measurement(InputLatency::DIAGNOSTIC, latency.as_secs_f64() * 1000.);
+    }
+}
+
+
+/// Update the debug overlay, if shown, with the FPS, input latency, ball velocity, rally length and
+/// opponent target, or with the input latency in more detail if just showing that
+pub fn debug_overlay(
+    mut debug_query: Query<(&mut Text, &DebugText)>,
+    diagnostics: Res<Diagnostics>,
+    ball_query: Query<&Velocity, With<Ball>>,
+    rally_count: Res<RallyCount>,
+    opponent_target: Res<OpponentTarget>,
+) {
+    let (mut text, debug_text) = match debug_query.get_single_mut() {
+        Ok(debug_text) => debug_text,
+        Err(_) => return,
+    };
+    if debug_text.latency_only {
+        text.sections[0].value = input_latency_readout(diagnostics.get(InputLatency::DIAGNOSTIC));
+        return;
+    }
+
+    let fps = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.average())
+        .map_or("-".to_string(), |fps| format!("{:.0}", fps));
+    let input_latency = diagnostics
+        .get(InputLatency::DIAGNOSTIC)
+        .and_then(|latency| latency.average())
+        .map_or("-".to_string(), |latency| format!("{:.1} ms", latency));
+    let ball_velocities: Vec<String> = ball_query
+        .iter()
+        .map(|velocity| format!("({:.0}, {:.0})", velocity.0.x, velocity.0.y))
+        .collect();
+    let target = opponent_target.0.map_or("-".to_string(), |target| format!("{:.0}", target));
+
+    text.sections[0].value = format!(
+        "FPS: {}\nInput latency: {}\nBall velocity: {}\nRally: {}\nOpponent target Y: {}",
+        fps,
+        input_latency,
+        if ball_velocities.is_empty() { "-".to_string() } else { ball_velocities.join(" ") },
+        rally_count.0,
+        target,
+    );
+}
+
+
+/// Input latency readout: the average, latest, and range of the recent measurements
+fn input_latency_readout(diagnostic: Option<&Diagnostic>) -> String {
+    let diagnostic = match diagnostic {
+        Some(diagnostic) if diagnostic.history_len() > 0 => diagnostic,
+        _ => return "Input latency: - (move the paddle)".to_string(),
+    };
+    let min = diagnostic.values().copied().fold(f64::INFINITY, f64::min);
+    let max = diagnostic.values().copied().fold(0., f64::max);
+    format!(
+        "Input latency: {:.1} ms\nLatest: {:.1} ms\nRange: {:.1}-{:.1} ms over {} inputs",
+        diagnostic.average().unwrap_or_default(),
+        diagnostic.value().unwrap_or_default(),
+        min,
+        max,
+        diagnostic.history_len(),
+    )
+}
+
+
+/// Mute or unmute all audio with the mute key (M by default)
+///
+/// The music is paused rather than stopped while muted, so it picks up where it left off.
+pub fn toggle_mute(
+    keyboard_input: Res<Input<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut audio_settings: ResMut<AudioSettings>,
+    music_sink: Res<MusicSink>,
+    audio_sinks: Res<Assets<AudioSink>>,
+) {
+    if !keyboard_input.just_pressed(key_bindings.mute) {
+        return;
+    }
+    audio_settings.muted = !audio_settings.muted;
+    apply_mute(&audio_settings, &music_sink, &audio_sinks);
+}
+
+
+/// Pause or resume the music to match the muted setting
+fn apply_mute(audio_settings: &AudioSettings, music_sink: &MusicSink, audio_sinks: &Assets<AudioSink>) {
+    if let Some(sink) = audio_sinks.get(&music_sink.0) {
+        if audio_settings.muted {
+            sink.pause();
+        } else {
+            sink.play();
+        }
+    }
+}
+
+
+/// Lower the music volume when a goal is scored, easing it back up afterwards
+///
+/// Also keeps the music volume in line with any change to the audio settings.
+pub fn duck_music(
+    mut collision_events: EventReader<CollisionEvent>,
+    time: Res<Time>,
+    mut music_duck: ResMut<MusicDuck>,
+    music_sink: Res<MusicSink>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    audio_settings: Res<AudioSettings>,
+) {
+    if collision_events.iter().any(|event| matches!(event, CollisionEvent::Goal)) {
+        music_duck.0.reset();
+    } else if music_duck.0.finished() && !audio_settings.is_changed() {
+        return;
+    }
+    music_duck.0.tick(time.delta());
+
+    if let Some(sink) = audio_sinks.get(&music_sink.0) {
+        sink.set_volume(music_duck.music_volume(&audio_settings));
+    }
+}
+
+
+/// Spawn the match point border and banner, hidden until match point
+pub fn spawn_match_point_emphasis(ui_scale: Res<UiScale>, asset_server: Res<AssetServer>, mut commands: Commands) {
+    for edge in [Vec2::X, -Vec2::X, Vec2::Y, -Vec2::Y] {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite { color: MATCH_POINT_BORDER_COLOR, ..default() },
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(MatchPointBorder(edge))
+            .insert(Gameplay);
+    }
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                "MATCH POINT",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: MATCH_POINT_FONT_SIZE * ui_scale.value(),
+                    color: MATCH_POINT_BORDER_COLOR,
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                },
+            ),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(MatchPointBanner)
+        .insert(ScaledText(MATCH_POINT_FONT_SIZE))
+        .insert(Gameplay);
+}
+
+
+/// Show the border (pulsing, unless motion is reduced) and banner on match point, if emphasized,
+/// fitting them to the arena
+#[allow(clippy::type_complexity)]
+pub fn show_match_point(
+    time: Res<Time>,
+    match_point: Res<MatchPoint>,
+    emphasis: Res<MatchPointEmphasis>,
+    reduce_motion: Res<ReduceMotion>,
+    arena: Res<Arena>,
+    mut border_query: Query<(&MatchPointBorder, &mut Transform, &mut Sprite, &mut Visibility)>,
+    mut banner_query: Query<(&mut Transform, &mut Visibility), (With<MatchPointBanner>, Without<MatchPointBorder>)>,
+) {
+    let shown = match_point.0 && emphasis.0;
+    let alpha = if reduce_motion.0 {
+        1.
+    } else {
+        let phase = time.seconds_since_startup() as f32 * MATCH_POINT_PULSE_RATE * std::f32::consts::TAU;
+        0.6 + 0.4 * phase.sin()
+    };
+
+    let half_extents = Vec2::new(arena.width, arena.height) * 0.5;
+    for (border, mut transform, mut sprite, mut visibility) in border_query.iter_mut() {
+        visibility.is_visible = shown;
+        if !shown {
+            continue;
+        }
+        // Just inside the arena's edge, spanning it
+        let inset = half_extents - Vec2::splat(MATCH_POINT_BORDER_THICKNESS * 0.5);
+        transform.translation = (border.0 * inset).extend(transform.translation.z);
+        sprite.custom_size = Some(if border.0.x == 0. {
+            Vec2::new(arena.width, MATCH_POINT_BORDER_THICKNESS)
+        } else {
+            Vec2::new(MATCH_POINT_BORDER_THICKNESS, arena.height)
+        });
+        sprite.color.set_a(alpha);
+    }
+    for (mut transform, mut visibility) in banner_query.iter_mut() {
+        visibility.is_visible = shown;
+        transform.translation.y = arena.height * 0.25;
+    }
+}
+
+
+/// Bring in the tense music layer on match point, if emphasized, and fade it out otherwise
+///
+/// Also keeps its volume in line with any change to the audio settings.
+pub fn play_match_point_music(
+    match_point: Res<MatchPoint>,
+    emphasis: Res<MatchPointEmphasis>,
+    audio_settings: Res<AudioSettings>,
+    match_point_music: Res<MatchPointMusicSink>,
+    audio_sinks: Res<Assets<AudioSink>>,
+) {
+    if !(match_point.is_changed() || emphasis.is_changed() || audio_settings.is_changed()) {
+        return;
+    }
+    if let Some(sink) = audio_sinks.get(&match_point_music.0) {
+        let volume = if match_point.0 && emphasis.0 { MATCH_POINT_MUSIC_VOLUME } else { 0. };
+        sink.set_volume(audio_settings.music_volume(volume));
+    }
+}
+
+
+/// Fade the music stems in as either side closes in on the winning score, silencing them outside of
+/// a match
+///
+/// Also keeps their volume in line with any change to the audio settings.
+pub fn play_music_stems(
+    app_state: Res<State<AppState>>,
+    scoreboard: Res<Scoreboard>,
+    winning_score: Res<WinningScore>,
+    audio_settings: Res<AudioSettings>,
+    music_stems: Res<MusicStems>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    mut last_intensity: Local<Option<f32>>,
+) {
+    let intensity = match app_state.current() {
+        AppState::Playing | AppState::Paused => MusicStems::intensity(&scoreboard, &winning_score),
+        _ => 0.,
+    };
+    if *last_intensity == Some(intensity) && !audio_settings.is_changed() {
+        return;
+    }
+    *last_intensity = Some(intensity);
+    for (index, stem) in music_stems.sinks.iter().enumerate() {
+        if let Some(sink) = audio_sinks.get(stem) {
+            sink.set_volume(music_stems.stem_volume(index, intensity, &audio_settings));
+        }
+    }
+}
+
+
+/// Resize and recolor stamina bars to reflect their paddle's current stamina
+pub fn update_stamina_bars(
+    stamina_query: Query<&Stamina>,
+    mut bar_query: Query<(&StaminaBar, &mut Sprite)>,
+) {
+    for (bar, mut sprite) in bar_query.iter_mut() {
+        if let Ok(stamina) = stamina_query.get(bar.0) {
+            let fraction = stamina.current / stamina.max;
+            sprite.custom_size = Some(Vec2::new(STAMINA_BAR_SIZE.x * fraction, STAMINA_BAR_SIZE.y));
+            sprite.color = if stamina.speed_factor() < 1. {
+                Color::rgb(0.9, 0.4, 0.2)
+            } else {
+                Color::rgb(0.3, 0.8, 0.4)
+            };
+        }
+    }
+}
+
+
+/// Accumulate the ball's position into the heatmap
+pub fn record_heatmap(
+    ball_query: Query<&Transform, With<Ball>>,
+    mut heatmap: ResMut<Heatmap>,
+    arena: Res<Arena>,
+) {
+    for transform in ball_query.iter() {
+        let column = ((transform.translation.x / arena.width + 0.5) * HEATMAP_COLUMNS as f32) as usize;
+        let row = ((transform.translation.y / arena.height + 0.5) * HEATMAP_ROWS as f32) as usize;
+        let index = row.min(HEATMAP_ROWS - 1) * HEATMAP_COLUMNS + column.min(HEATMAP_COLUMNS - 1);
+        heatmap.counts[index] += 1;
+    }
+}
+
+
+/// Start each match with an empty heatmap
+pub fn reset_heatmap(mut heatmap: ResMut<Heatmap>) {
+    heatmap.counts.fill(0);
+}
+
+
+/// Add where the paddles and balls are this tick to the replay buffer
+pub fn record_replay_frame(
+    player_query: Query<&Transform, With<Player>>,
+    opponent_query: Query<&Transform, With<Opponent>>,
+    ball_query: Query<&Transform, With<Ball>>,
+    mut replay_buffer: ResMut<ReplayBuffer>,
+) {
+    let paddle_y = |transform: Option<&Transform>| transform.map_or(0., |transform| transform.translation.y);
+    replay_buffer.push(ReplayFrame {
+        player_y: paddle_y(player_query.iter().next()),
+        opponent_y: paddle_y(opponent_query.iter().next()),
+        balls: ball_query.iter().map(|transform| transform.translation.truncate()).collect(),
+    });
+}
+
+
+/// Start each match with an empty replay buffer
+pub fn reset_replay_buffer(mut replay_buffer: ResMut<ReplayBuffer>) {
+    replay_buffer.frames.clear();
+}
+
+
+/// Start playing back the replay buffer while paused with the replay key (R by default), or stop it
+/// early with another press
+pub fn toggle_instant_replay(
+    keyboard_input: Res<Input<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    disconnect_pause: Res<DisconnectPause>,
+    replay_buffer: Res<ReplayBuffer>,
+    mut playback: ResMut<ReplayPlayback>,
+) {
+    if !keyboard_input.just_pressed(key_bindings.instant_replay) || disconnect_pause.waiting {
+        return;
+    }
+    playback.0 = match playback.0 {
+        None if !replay_buffer.frames.is_empty() => Some(0.),
+        _ => None,
+    };
+}
+
+
+/// Stop any replay on resuming
+pub fn stop_instant_replay(mut playback: ResMut<ReplayPlayback>) {
+    playback.0 = None;
+}
+
+
+/// Show each frame of the replay buffer in turn while a replay plays, with ghosts standing in for the
+/// paddles and balls (which are hidden) and the pause overlay cleared, putting everything back once
+/// it's over
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn play_instant_replay(
+    time: Res<Time>,
+    replay_buffer: Res<ReplayBuffer>,
+    mut playback: ResMut<ReplayPlayback>,
+    paddle_query: Query<(&Transform, &Sprite, Option<&Player>), Or<(With<Player>, With<Opponent>)>>,
+    mut hidden_query: Query<&mut Visibility, Or<(With<Ball>, With<Player>, With<Opponent>)>>,
+    ghost_query: Query<Entity, With<ReplayGhost>>,
+    mut overlay_query: Query<(&mut UiColor, &Children), With<PauseUi>>,
+    mut text_query: Query<&mut Text>,
+    mut was_playing: Local<bool>,
+    mut commands: Commands,
+) {
+    let frame = playback.0.and_then(|elapsed| replay_buffer.frame_at(elapsed));
+    let playing = frame.is_some();
+    if !playing && !*was_playing {
+        return;
+    }
+
+    for ghost in ghost_query.iter() {
+        commands.entity(ghost).despawn();
+    }
+    let mut spawn_ghost = |position: Vec2, size: Vec2, color: Color| {
+        commands
+            .spawn_bundle(SpriteBundle {
+                transform: Transform::from_translation(position.extend(0.)),
+                sprite: Sprite { color, custom_size: Some(size), ..default() },
+                ..default()
+            })
+            .insert(ReplayGhost)
+            .insert(Gameplay);
+    };
+    if let Some(frame) = frame {
+        for (transform, sprite, player) in paddle_query.iter() {
+            let y = if player.is_some() { frame.player_y } else { frame.opponent_y };
+            spawn_ghost(Vec2::new(transform.translation.x, y), sprite.custom_size.unwrap(), REPLAY_GHOST_COLOR);
+        }
+        for &ball in &frame.balls {
+            spawn_ghost(ball, BALL_SIZE, REPLAY_GHOST_COLOR);
+        }
+        playback.0 = playback.0.map(|elapsed| elapsed + time.delta_seconds());
+    } else {
+        // Ran out
+        playback.0 = None;
+    }
+
+    if playing != *was_playing {
+        for mut visibility in hidden_query.iter_mut() {
+            visibility.is_visible = !playing;
+        }
+        for (mut color, children) in overlay_query.iter_mut() {
+            *color = if playing { Color::NONE } else { PAUSE_OVERLAY_COLOR }.into();
+            for &child in children.iter() {
+                if let Ok(mut text) = text_query.get_mut(child) {
+                    text.sections[0].value = if playing { "REPLAY" } else { "PAUSED" }.to_string();
+                }
+            }
+        }
+        *was_playing = playing;
+    }
+}
+
+
+/// Show/hide the heatmap overlay, and clear the recorded history, with their bound keys
+pub fn toggle_heatmap(
+    keyboard_input: Res<Input<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut heatmap: ResMut<Heatmap>,
+    cell_query: Query<Entity, With<HeatmapCell>>,
+    arena: Res<Arena>,
+    mut commands: Commands,
+) {
+    if keyboard_input.just_pressed(key_bindings.clear_heatmap) {
+        heatmap.counts.fill(0);
+    }
+
+    if !keyboard_input.just_pressed(key_bindings.toggle_heatmap) {
+        return;
+    }
+    heatmap.visible = !heatmap.visible;
+
+    if !heatmap.visible {
+        for cell in cell_query.iter() {
+            commands.entity(cell).despawn();
+        }
+        return;
+    }
+
+    for index in 0..heatmap.counts.len() {
+        let (translation, cell_size) = heatmap_cell_layout(&arena, index);
+        commands
+            .spawn()
+            .insert(HeatmapCell(index))
+            .insert_bundle(SpriteBundle {
+                transform: Transform {
+                    translation,
+                    ..default()
+                },
+                sprite: Sprite {
+                    color: Color::NONE,
+                    custom_size: Some(cell_size),
+                    ..default()
+                },
+                ..default()
+            });
+    }
+}
+
+
+/// Position and size of a heatmap overlay cell, covering its share of the arena
+fn heatmap_cell_layout(arena: &Arena, index: usize) -> (Vec3, Vec2) {
+    let cell_size = Vec2::new(
+        arena.width / HEATMAP_COLUMNS as f32,
+        arena.height / HEATMAP_ROWS as f32,
+    );
+    let column = (index % HEATMAP_COLUMNS) as f32;
+    let row = (index / HEATMAP_COLUMNS) as f32;
+    let translation = Vec3::new(
+        -arena.width * 0.5 + (column + 0.5) * cell_size.x,
+        -arena.height * 0.5 + (row + 0.5) * cell_size.y,
+        // Drawn behind everything else
+        -1.0,
+    );
+    (translation, cell_size)
+}
+
+
+/// Color heatmap overlay cells relative to the most visited cell
+pub fn update_heatmap_cells(
+    heatmap: Res<Heatmap>,
+    mut cell_query: Query<(&HeatmapCell, &mut Sprite)>,
+) {
+    if !heatmap.visible {
+        return;
+    }
+
+    let max_count = heatmap.counts.iter().copied().max().unwrap_or(0).max(1) as f32;
+    for (cell, mut sprite) in cell_query.iter_mut() {
+        let heat = heatmap.counts[cell.0] as f32 / max_count;
+        sprite.color = Color::rgba(1.0, 0.4 * (1. - heat), 0.1, heat * HEATMAP_MAX_ALPHA);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::Events;
+    use crate::testing::*;
+
+    #[test]
+    fn ball_bounces_off_top_wall() {
+        let mut app = headless_app();
+        let ball = spawn_test_ball(&mut app, Vec2::new(0., 280.), Vec2::new(100., 300.));
+        step(&mut app, 10);
+
+        let transform = app.world.get::<Transform>(ball).unwrap();
+        assert!(velocity(&app, ball).y < 0.);
+        assert!(transform.translation.y < 280.);
+        assert!((velocity(&app, ball).x - 100.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn ball_bounces_off_bottom_wall() {
+        let mut app = headless_app();
+        let ball = spawn_test_ball(&mut app, Vec2::new(0., -280.), Vec2::new(-100., -300.));
+        step(&mut app, 10);
+
+        assert!(velocity(&app, ball).y > 0.);
+        assert!(app.world.get::<Transform>(ball).unwrap().translation.y > -280.);
+    }
+
+    #[test]
+    fn ball_in_left_gutter_scores_for_opponent() {
+        let mut app = headless_app();
+        let transform = simulate_ball(&mut app, Vec2::new(-360., 200.), Vec2::new(-300., 0.), 10);
+
+        assert!(transform.is_none());
+        let scoreboard = app.world.resource::<Scoreboard>();
+        assert_eq!((scoreboard.player, scoreboard.opponent), (0, 1));
+    }
+
+    #[test]
+    fn ball_in_right_gutter_scores_for_player() {
+        let mut app = headless_app();
+        let transform = simulate_ball(&mut app, Vec2::new(360., 200.), Vec2::new(300., 0.), 10);
+
+        assert!(transform.is_none());
+        let scoreboard = app.world.resource::<Scoreboard>();
+        assert_eq!((scoreboard.player, scoreboard.opponent), (1, 0));
+    }
+
+    #[test]
+    fn physics_only_runs_the_ticks_stepped() {
+        let mut app = headless_app();
+        let transform = simulate_ball(&mut app, Vec2::ZERO, Vec2::new(60., 0.), 3).unwrap();
+        assert!((transform.translation.x - 60. * TIME_STEP * 3.).abs() < 1e-4);
+
+        app.update();
+        let ball = app.world.query_filtered::<&Transform, With<Ball>>().iter(&app.world).next().unwrap();
+        assert!((ball.translation.x - 60. * TIME_STEP * 3.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn fresh_serve_cant_score_against_its_server() {
+        let mut app = headless_app();
+        let ball = spawn_test_ball(&mut app, Vec2::new(-360., 200.), Vec2::new(-300., 0.));
+        app.world.entity_mut(ball).insert(Serve {
+            immunity: Timer::from_seconds(SERVE_GUTTER_IMMUNITY, false),
+            by_player: true,
+        });
+        step(&mut app, 10);
+
+        assert!(velocity(&app, ball).x > 0.);
+        assert_eq!(app.world.resource::<Scoreboard>().opponent, 0);
+    }
+
+    #[test]
+    fn serve_immunity_runs_out_after_its_window() {
+        let mut app = headless_app();
+        let ball = spawn_test_ball(&mut app, Vec2::new(-200., 200.), Vec2::new(-300., 0.));
+        app.world.entity_mut(ball).insert(Serve {
+            immunity: Timer::from_seconds(SERVE_GUTTER_IMMUNITY, false),
+            by_player: true,
+        });
+        // Reaches the gutter after the window (well over 30 ticks away)
+        step(&mut app, 60);
+
+        assert!(app.world.get_entity(ball).is_none());
+        assert_eq!(app.world.resource::<Scoreboard>().opponent, 1);
+    }
+
+    /// Positions of everything the seeded randomness decides, after playing out a match from a seed
+    fn play_seeded_match(seed: u64) -> Vec<Vec3> {
+        let mut app = headless_app();
+        app.insert_resource(GameRng::new(seed))
+            .insert_resource(RandomServe { enabled: true, max_angle: 0.35 })
+            .insert_resource(PowerUps { enabled: true, spawn_timer: Timer::from_seconds(1.0, true) });
+        app.world.resource_mut::<BallSpawnTimer>().0.reset();
+        step(&mut app, 600);
+
+        let mut query = app.world.query_filtered::<&Transform, Or<(With<Ball>, With<PowerUp>)>>();
+        query.iter(&app.world).map(|transform| transform.translation).collect()
+    }
+
+    #[test]
+    fn same_seed_plays_out_the_same_match() {
+        let positions = play_seeded_match(7);
+        assert!(!positions.is_empty());
+        assert_eq!(positions, play_seeded_match(7));
+        assert_ne!(positions, play_seeded_match(8));
+    }
+
+    fn player_y(app: &mut App) -> f32 {
+        let mut query = app.world.query_filtered::<&Transform, With<Player>>();
+        query.iter(&app.world).next().unwrap().translation.y
+    }
+
+    fn ball_position(app: &mut App) -> Option<Vec3> {
+        let mut query = app.world.query_filtered::<&Transform, With<Ball>>();
+        query.iter(&app.world).next().map(|transform| transform.translation)
+    }
+
+    fn app_state(app: &App) -> AppState {
+        *app.world.resource::<State<AppState>>().current()
+    }
+
+    /// Press (or release) a key for the next frame
+    fn press(app: &mut App, key: KeyCode, pressed: bool) {
+        let mut keyboard_input = app.world.resource_mut::<Input<KeyCode>>();
+        keyboard_input.clear();
+        if pressed {
+            keyboard_input.press(key);
+        } else {
+            keyboard_input.release(key);
+        }
+    }
+
+    #[test]
+    fn replay_plays_back_recorded_input_serves_and_pauses() {
+        let mut recording = headless_app();
+        recording
+            .insert_resource(ReplayMode::Recording)
+            .insert_resource(InputMode::Keyboard)
+            .insert_resource(ServeOnDemand(true))
+            .insert_resource(PlayerTurn(false));
+        recording.world.resource_mut::<Replay>().input_mode = InputMode::Keyboard;
+        recording.world.resource_mut::<BallSpawnTimer>().0.reset();
+
+        let mut recorded_path = Vec::new();
+        for tick in 0..60 {
+            match tick {
+                5 => press(&mut recording, KeyCode::W, true),
+                10 => press(&mut recording, KeyCode::Space, true),
+                11 => press(&mut recording, KeyCode::Space, false),
+                30 => press(&mut recording, KeyCode::W, false),
+                40 => press(&mut recording, KeyCode::S, true),
+                _ => recording.world.resource_mut::<Input<KeyCode>>().clear(),
+            }
+            if tick == 25 {
+                recording.world.resource_mut::<State<AppState>>().push(AppState::Paused).unwrap();
+                recording.update();
+                recording.world.resource_mut::<State<AppState>>().pop().unwrap();
+                recording.update();
+            }
+            step(&mut recording, 1);
+            recorded_path.push(player_y(&mut recording));
+        }
+        let recorded_ball = ball_position(&mut recording);
+        assert!(recorded_ball.is_some());
+        let replay = recording.world.remove_resource::<Replay>().unwrap();
+        assert_eq!(replay.pauses, vec![25]);
+
+        let mut playback = headless_app();
+        playback
+            .insert_resource(ReplayMode::Playback)
+            .insert_resource(replay)
+            .insert_resource(ServeOnDemand(true))
+            .insert_resource(PlayerTurn(false));
+        playback.world.resource_mut::<BallSpawnTimer>().0.reset();
+
+        let mut played_path = Vec::new();
+        let mut paused_at = None;
+        while played_path.len() < recorded_path.len() {
+            let cursor = playback.world.resource::<Replay>().cursor;
+            step(&mut playback, 1);
+            if app_state(&playback) == AppState::Paused {
+                paused_at = Some(cursor);
+                playback.world.resource_mut::<State<AppState>>().pop().unwrap();
+                playback.update();
+            }
+            if playback.world.resource::<Replay>().cursor != cursor {
+                played_path.push(player_y(&mut playback));
+            }
+        }
+
+        assert_eq!(paused_at, Some(25));
+        assert_eq!(played_path, recorded_path);
+        assert_eq!(ball_position(&mut playback), recorded_ball);
+    }
+
+    #[test]
+    fn reaching_the_winning_score_ends_the_game() {
+        let mut app = headless_app();
+        app.world.resource_mut::<Scoreboard>().player = 11;
+        app.update();
+
+        assert_eq!(app_state(&app), AppState::GameOver);
+        assert_eq!(app.world.resource::<Series>().player_matches, 1);
+    }
+
+    /// Pause in the same frame as anything else, the first time it's playing
+    fn pause_once(mut app_state: ResMut<State<AppState>>, mut paused: Local<bool>) {
+        if !*paused && *app_state.current() == AppState::Playing {
+            app_state.push(AppState::Paused).unwrap();
+            *paused = true;
+        }
+    }
+
+    #[test]
+    fn winning_in_the_same_frame_as_pausing_ends_the_game_after_it() {
+        let mut app = headless_app();
+        app.add_system(pause_once.before(check_game_over));
+        app.world.resource_mut::<Scoreboard>().opponent = 11;
+        app.update();
+        assert_eq!(app_state(&app), AppState::Paused);
+
+        app.world.resource_mut::<State<AppState>>().pop().unwrap();
+        app.update();
+        assert_eq!(app_state(&app), AppState::GameOver);
+    }
+
+    #[test]
+    fn stuck_ball_is_only_served_again_once_no_balls_are_left() {
+        let mut app = headless_app();
+        let stuck = spawn_test_ball(&mut app, Vec2::new(0., 100.), Vec2::ZERO);
+        let moving = spawn_test_ball(&mut app, Vec2::new(0., -100.), Vec2::new(0., 200.));
+        step(&mut app, (STUCK_STILL_TIME / TIME_STEP) as u32 + 10);
+
+        assert!(app.world.get_entity(stuck).is_none());
+        assert!(app.world.get_entity(moving).is_some());
+        assert!(app.world.resource::<BallSpawnTimer>().0.finished());
+        assert_eq!(app.world.resource::<MatchStats>().rallies, 0);
+    }
+
+    #[test]
+    fn last_stuck_ball_ends_the_rally() {
+        let mut app = headless_app();
+        let stuck = spawn_test_ball(&mut app, Vec2::new(0., 100.), Vec2::ZERO);
+        step(&mut app, (STUCK_STILL_TIME / TIME_STEP) as u32 + 10);
+
+        assert!(app.world.get_entity(stuck).is_none());
+        assert!(!app.world.resource::<BallSpawnTimer>().0.finished());
+        assert_eq!(app.world.resource::<MatchStats>().rallies, 1);
+    }
+
+    #[test]
+    fn every_match_of_a_series_reports_its_final_score() {
+        let mut app = headless_app();
+        app.world.resource_mut::<Series>().best_of = 3;
+        app.world.resource_mut::<Scoreboard>().player = 11;
+        app.world.resource_mut::<Scoreboard>().opponent = 4;
+        app.update();
+
+        let events = app.world.resource::<Events<MatchEnded>>();
+        let scores: Vec<(u16, u16)> = events
+            .get_reader()
+            .iter(events)
+            .map(|MatchEnded(score, _)| (score.player, score.opponent))
+            .collect();
+        assert_eq!(scores, vec![(11, 4)]);
+        // The series goes on, with the score reset for the next match
+        assert_eq!(app_state(&app), AppState::Playing);
+        assert_eq!(app.world.resource::<Scoreboard>().player, 0);
+    }
+
+    #[test]
+    fn heatmap_is_cleared_for_each_new_match() {
+        let mut app = headless_app();
+        simulate_ball(&mut app, Vec2::ZERO, Vec2::new(100., 0.), 10);
+        assert!(app.world.resource::<Heatmap>().counts.iter().sum::<u32>() > 0);
+
+        app.world.resource_mut::<State<AppState>>().set(AppState::GameOver).unwrap();
+        app.update();
+        assert!(app.world.resource::<Heatmap>().counts.iter().sum::<u32>() > 0);
+        app.world.resource_mut::<State<AppState>>().set(AppState::Playing).unwrap();
+        app.update();
+        assert_eq!(app.world.resource::<Heatmap>().counts.iter().sum::<u32>(), 0);
+    }
+
+    #[test]
+    fn heatmap_keys_follow_their_bindings() {
+        let mut app = headless_app();
+        app.add_system(toggle_heatmap);
+        {
+            let mut key_bindings = app.world.resource_mut::<KeyBindings>();
+            assert!(key_bindings.bind(KeyAction::ToggleHeatmap, KeyCode::J));
+            assert!(!key_bindings.bind(KeyAction::ClearHeatmap, KeyCode::J));
+        }
+
+        press(&mut app, KeyCode::H, true);
+        app.update();
+        assert!(!app.world.resource::<Heatmap>().visible);
+
+        press(&mut app, KeyCode::J, true);
+        app.update();
+        assert!(app.world.resource::<Heatmap>().visible);
+
+        app.world.resource_mut::<Heatmap>().counts[0] = 5;
+        press(&mut app, KeyCode::C, true);
+        app.update();
+        assert_eq!(app.world.resource::<Heatmap>().counts[0], 0);
+    }
+
+    /// Place a collider of the given kind, as a plain square sprite
+    fn spawn_collider(app: &mut App, kind: ColliderKind, position: Vec2) -> Entity {
+        app.world
+            .spawn()
+            .insert(kind)
+            .insert_bundle(SpriteBundle {
+                transform: Transform::from_translation(position.extend(0.)),
+                sprite: Sprite {
+                    custom_size: Some(Vec2::new(16., 16.)),
+                    ..default()
+                },
+                ..default()
+            })
+            .id()
+    }
+
+    #[test]
+    fn paddle_bounces_the_ball_back_at_an_angle() {
+        let mut app = headless_app();
+        let mut query = app.world.query_filtered::<&Transform, With<Player>>();
+        let paddle = query.iter(&app.world).next().unwrap().translation;
+        let ball = spawn_test_ball(&mut app, Vec2::new(paddle.x + 20., paddle.y + 10.), Vec2::new(-300., 0.));
+        step(&mut app, 10);
+
+        let velocity = velocity(&app, ball);
+        assert!(velocity.x > 0.);
+        assert!(velocity.y > 0.);
+    }
+
+    #[test]
+    fn obstacle_deflects_the_ball_straight_back() {
+        let mut app = headless_app();
+        spawn_collider(&mut app, ColliderKind::Obstacle, Vec2::new(50., 0.));
+        let ball = spawn_test_ball(&mut app, Vec2::ZERO, Vec2::new(300., 0.));
+        step(&mut app, 10);
+
+        assert_eq!(velocity(&app, ball), Vec2::new(-300., 0.));
+    }
+
+    #[test]
+    fn target_is_knocked_out_without_deflecting_the_ball() {
+        let mut app = headless_app();
+        let target = spawn_collider(&mut app, ColliderKind::Target, Vec2::new(50., 0.));
+        let ball = spawn_test_ball(&mut app, Vec2::ZERO, Vec2::new(300., 0.));
+        step(&mut app, 10);
+
+        assert!(app.world.get_entity(target).is_none());
+        assert_eq!(velocity(&app, ball), Vec2::new(300., 0.));
+    }
+
+    #[test]
+    fn power_up_enlarges_the_paddle_that_last_touched_the_ball() {
+        let mut app = headless_app();
+        let mut query = app.world.query_filtered::<Entity, With<Player>>();
+        let paddle = query.iter(&app.world).next().unwrap();
+        let power_up = spawn_collider(&mut app, ColliderKind::PowerUp, Vec2::new(50., 0.));
+        app.world.entity_mut(power_up).insert(PowerUp);
+        let ball = spawn_test_ball(&mut app, Vec2::ZERO, Vec2::new(300., 0.));
+        app.world.entity_mut(ball).insert(LastTouched(paddle));
+        step(&mut app, 10);
+
+        assert!(app.world.get_entity(power_up).is_none());
+        assert_eq!(velocity(&app, ball), Vec2::new(300., 0.));
+        assert!(app.world.get::<Enlarged>(paddle).is_some());
+        let paddle_size = app.world.resource::<GameConfig>().paddle_size;
+        let size = app.world.get::<Sprite>(paddle).unwrap().custom_size.unwrap();
+        assert_eq!(size, paddle_size * Vec2::new(1., POWER_UP_PADDLE_SCALE));
+    }
+
+    #[test]
+    fn changing_the_ui_scale_resizes_all_scaled_text() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(UiScale(1.0))
+            .insert_resource(Arena { width: DEFAULT_ARENA_WIDTH, height: DEFAULT_ARENA_HEIGHT })
+            .add_system(apply_ui_scale);
+        let score = app.world
+            .spawn()
+            .insert(Text::with_section("0", TextStyle { font_size: SCORE_FONT_SIZE, ..default() }, default()))
+            .insert(Style::default())
+            .insert(ScaledText(SCORE_FONT_SIZE))
+            .insert(ScoreText)
+            .id();
+        let heading = app.world
+            .spawn()
+            .insert(Text::with_section("PAUSED", TextStyle { font_size: HEADING_FONT_SIZE, ..default() }, default()))
+            .insert(ScaledText(HEADING_FONT_SIZE))
+            .id();
+        let font_size = |app: &App, entity| app.world.get::<Text>(entity).unwrap().sections[0].style.font_size;
+
+        app.world.resource_mut::<UiScale>().0 = 1.5;
+        app.update();
+        assert_eq!(font_size(&app, score), SCORE_FONT_SIZE * 1.5);
+        assert_eq!(font_size(&app, heading), HEADING_FONT_SIZE * 1.5);
+
+        // Clamped to the usable range
+        app.world.resource_mut::<UiScale>().0 = 10.0;
+        app.update();
+        assert_eq!(font_size(&app, heading), HEADING_FONT_SIZE * 2.0);
+    }
+
+    #[test]
+    fn changing_the_ui_scale_resizes_menu_buttons() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(UiScale(1.0))
+            .insert_resource(Arena { width: DEFAULT_ARENA_WIDTH, height: DEFAULT_ARENA_HEIGHT })
+            .add_system(apply_ui_scale);
+        let button = app.world
+            .spawn()
+            .insert(menu_button_style(&UiScale(1.0)))
+            .insert(MenuButton { action: MenuAction::Back, index: 0 })
+            .id();
+
+        app.world.resource_mut::<UiScale>().0 = 1.5;
+        app.update();
+        let style = app.world.get::<Style>(button).unwrap();
+        let size = MENU_BUTTON_SIZE * 1.5;
+        assert_eq!(style.size, Size::new(Val::Px(size.x), Val::Px(size.y)));
+        assert_eq!(style.margin, Rect::all(Val::Px(MENU_BUTTON_MARGIN * 1.5)));
+    }
+
+    #[test]
+    fn double_game_speed_doubles_ball_travel() {
+        let travel = |game_speed| {
+            let mut app = headless_app();
+            app.insert_resource(GameSpeed(game_speed));
+            let ball = serve_ball(&mut app);
+            step(&mut app, 10);
+            app.world.get::<Transform>(ball).unwrap().translation.length()
+        };
+
+        assert!((travel(2.0) - 2. * travel(1.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn double_game_speed_doubles_opponent_speed() {
+        let opponent_speed = |game_speed| {
+            let mut app = headless_app();
+            app.insert_resource(GameSpeed(game_speed));
+            // No momentum, so the paddle takes on its target speed straight away
+            app.world.resource_mut::<GameConfig>().paddle_max_accel = f32::INFINITY;
+            let mut query = app.world.query_filtered::<(Entity, &mut Transform), With<Opponent>>();
+            let (opponent, mut transform) = query.iter_mut(&mut app.world).next().unwrap();
+            transform.translation.y = 100.;
+            step(&mut app, 1);
+            velocity(&app, opponent).y
+        };
+
+        assert!(opponent_speed(1.0) < 0.);
+        assert_eq!(opponent_speed(2.0), 2. * opponent_speed(1.0));
+    }
+
+    /// Score a goal with the scorer-serves rule, returning the X velocity of the next serve
+    fn serve_after_goal_by_player(player_scores: bool) -> f32 {
+        let mut app = headless_app();
+        app.insert_resource(ServeRule::ScorerServes);
+        let direction = if player_scores { 1. } else { -1. };
+        simulate_ball(&mut app, Vec2::new(direction * 360., 200.), Vec2::new(direction * 300., 0.), 10);
+        let ball = serve_ball(&mut app);
+        velocity(&app, ball).x
+    }
+
+    #[test]
+    fn scorer_serves_after_each_goal() {
+        // Player scoring serves toward the opponent, and the opponent scoring serves toward the player
+        assert!(serve_after_goal_by_player(true) > 0.);
+        assert!(serve_after_goal_by_player(false) < 0.);
+    }
+
+    #[test]
+    fn half_travel_keeps_the_player_paddle_in_the_middle_half() {
+        let mut app = headless_app();
+        app.insert_resource(InputMode::Keyboard).insert_resource(PlayerTravelFraction(0.5));
+        let quarter_height = DEFAULT_ARENA_HEIGHT * 0.25;
+
+        press(&mut app, KeyCode::W, true);
+        step(&mut app, 120);
+        let top = player_y(&mut app) + PADDLE_SIZE.y * 0.5;
+        assert!(top <= quarter_height && top > quarter_height - 10.);
+
+        press(&mut app, KeyCode::W, false);
+        press(&mut app, KeyCode::S, true);
+        step(&mut app, 120);
+        let bottom = player_y(&mut app) - PADDLE_SIZE.y * 0.5;
+        assert!(bottom >= -quarter_height && bottom < -quarter_height + 10.);
+    }
+
+    #[test]
+    fn idle_opponent_settles_at_its_rest_bias() {
+        let mut app = headless_app();
+        app.insert_resource(AiRestBias(60.));
+        let mut query = app.world.query_filtered::<(Entity, &mut Transform), With<Opponent>>();
+        let (opponent, mut transform) = query.iter_mut(&mut app.world).next().unwrap();
+        transform.translation.y = -100.;
+        step(&mut app, 300);
+
+        let y = app.world.get::<Transform>(opponent).unwrap().translation.y;
+        assert!((y - 60.).abs() < 1.);
+        assert!(velocity(&app, opponent).y.abs() < 1.);
+    }
+
+    #[test]
+    fn practice_reset_recenters_paddles_and_serves_again_keeping_the_score() {
+        let mut app = headless_app();
+        app.add_system(reset_rally.with_run_criteria(practice_helpers_enabled));
+        app.insert_resource(PracticeMode(true))
+            .insert_resource(Scoreboard { player: 3, opponent: 2 });
+        serve_ball(&mut app);
+        let mut query = app.world.query_filtered::<&mut Transform, Or<(With<Player>, With<Opponent>)>>();
+        for mut transform in query.iter_mut(&mut app.world) {
+            transform.translation.y = 120.;
+        }
+
+        press(&mut app, KeyCode::Back, true);
+        app.update();
+        assert!(ball_position(&mut app).is_none());
+        assert!(query.iter(&app.world).all(|transform| transform.translation.y == 0.));
+
+        press(&mut app, KeyCode::Back, false);
+        step(&mut app, (SERVE_COUNTDOWN / TIME_STEP) as u32 + 5);
+        assert!(ball_position(&mut app).is_some());
+        let scoreboard = app.world.resource::<Scoreboard>();
+        assert_eq!((scoreboard.player, scoreboard.opponent), (3, 2));
+    }
+
+    #[test]
+    fn smoothed_opponent_eases_into_its_target_velocity() {
+        let opponent_velocities = |smoothing| {
+            let mut app = headless_app();
+            app.insert_resource(AiSmoothing(smoothing));
+            app.world.resource_mut::<GameConfig>().paddle_max_accel = f32::INFINITY;
+            let mut query = app.world.query_filtered::<(Entity, &mut Transform), With<Opponent>>();
+            let (opponent, mut transform) = query.iter_mut(&mut app.world).next().unwrap();
+            transform.translation.y = 100.;
+            (0..3).map(|_| {
+                step(&mut app, 1);
+                velocity(&app, opponent).y
+            }).collect::<Vec<_>>()
+        };
+
+        // Without smoothing it heads back to rest at full speed straight away
+        let instant = opponent_velocities(0.)[0];
+        let smoothed = opponent_velocities(0.8);
+        assert!(smoothed[0] > instant * 0.5);
+        assert!(smoothed[0] > smoothed[1] && smoothed[1] > smoothed[2]);
+    }
+
+    #[test]
+    fn sticky_paddle_catches_the_ball_and_releases_it() {
+        let mut app = headless_app();
+        app.insert_resource(StickyPaddle(true));
+        app.world.resource_mut::<Input<MouseButton>>().press(MouseButton::Left);
+        let ball = spawn_test_ball(&mut app, Vec2::new(-340., 0.), Vec2::new(-300., 0.));
+        step(&mut app, 10);
+        assert!(app.world.get::<Caught>(ball).is_some());
+        assert_eq!(velocity(&app, ball), Vec2::ZERO);
+
+        // Held against the paddle as it moves
+        let mut query = app.world.query_filtered::<&mut Transform, With<Player>>();
+        query.iter_mut(&mut app.world).next().unwrap().translation.y = 50.;
+        step(&mut app, 1);
+        let held_y = app.world.get::<Transform>(ball).unwrap().translation.y;
+        assert!((held_y - 50.).abs() < PADDLE_SIZE.y * 0.5);
+
+        app.world.resource_mut::<Input<MouseButton>>().release(MouseButton::Left);
+        step(&mut app, 1);
+        assert!(app.world.get::<Caught>(ball).is_none());
+        assert!(velocity(&app, ball).x > 0.);
+    }
+
+    #[test]
+    fn handicapped_opponent_tracks_at_full_gain_once_the_ramp_is_over() {
+        let mut app = headless_app();
+        app.world.resource_mut::<GameConfig>().paddle_max_accel = f32::INFINITY;
+        let mut query = app.world.query_filtered::<(Entity, &mut Transform, &mut Velocity), With<Opponent>>();
+        let mut opponent_velocity_toward_ball = |app: &mut App| {
+            let (opponent, mut transform, mut velocity) = query.iter_mut(&mut app.world).next().unwrap();
+            transform.translation.y = 20.;
+            velocity.0 = Vec2::ZERO;
+            step(app, 1);
+            app.world.get::<Velocity>(opponent).unwrap().0.y
+        };
+
+        // Straight across toward the opponent, slowly enough to still be on its way at the end
+        spawn_test_ball(&mut app, Vec2::new(-300., 0.), Vec2::new(100., 0.));
+        let full = opponent_velocity_toward_ball(&mut app);
+        app.world.resource_mut::<AiHandicap>().initial_factor = 0.5;
+        app.world.resource_mut::<AiHandicap>().ramp.reset();
+        let handicapped = opponent_velocity_toward_ball(&mut app);
+        assert!(handicapped > full * 0.6);
+
+        step(&mut app, 200);
+        assert_eq!(opponent_velocity_toward_ball(&mut app), full);
+    }
+
+    /// Collision events sent in the last frame, in order, as "wall", "paddle" or "goal"
+    fn collision_events(app: &App) -> Vec<&'static str> {
+        let events = app.world.resource::<Events<CollisionEvent>>();
+        events
+            .get_reader()
+            .iter(events)
+            .map(|event| match event {
+                CollisionEvent::WallBounce => "wall",
+                CollisionEvent::PaddleBounce(_) => "paddle",
+                CollisionEvent::Goal => "goal",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wall_bounce_comes_before_a_simultaneous_paddle_bounce() {
+        let mut app = headless_app();
+        let mut query = app.world.query_filtered::<&mut Transform, With<Player>>();
+        query.iter_mut(&mut app.world).next().unwrap().translation.y = 272.;
+        // Ends the tick against the top wall, and just over the top corner of the paddle's face
+        let ball = spawn_test_ball(&mut app, Vec2::new(-363., 296.), Vec2::new(-300., 60.));
+        step(&mut app, 1);
+
+        assert_eq!(collision_events(&app), ["wall", "paddle"]);
+        assert!(velocity(&app, ball).x > 0.);
+    }
+
+    #[test]
+    fn paddle_bounce_saves_a_ball_overlapping_the_gutter() {
+        let mut app = headless_app();
+        // Fast enough to end the tick through the paddle and into the gutter behind it
+        let ball = spawn_test_ball(&mut app, Vec2::new(-370.5, 0.), Vec2::new(-600., 0.));
+        step(&mut app, 1);
+
+        assert_eq!(collision_events(&app), ["paddle"]);
+        assert!(velocity(&app, ball).x > 0.);
+        let scoreboard = app.world.resource::<Scoreboard>();
+        assert_eq!((scoreboard.player, scoreboard.opponent), (0, 0));
+    }
+
+    #[test]
+    fn wall_bounce_comes_before_a_simultaneous_goal() {
+        let mut app = headless_app();
+        let ball = spawn_test_ball(&mut app, Vec2::new(385., 296.), Vec2::new(300., 60.));
+        step(&mut app, 1);
+
+        assert_eq!(collision_events(&app), ["wall", "goal"]);
+        assert!(app.world.get_entity(ball).is_none());
+        assert_eq!(app.world.resource::<Scoreboard>().player, 1);
+    }
+
+    #[test]
+    fn frantic_movement_tires_the_paddle_out_until_it_rests() {
+        let mut app = headless_app();
+        app.insert_resource(InputMode::Keyboard);
+        let mut query = app.world.query_filtered::<Entity, With<Player>>();
+        let player = query.iter(&app.world).next().unwrap();
+        app.world.entity_mut(player).insert(Stamina { current: STAMINA_MAX, max: STAMINA_MAX });
+
+        // Up and down, never long enough to reach either end
+        let mut top_speed: f32 = 0.;
+        for rep in 0..20 {
+            press(&mut app, if rep % 2 == 0 { KeyCode::W } else { KeyCode::S }, true);
+            for _ in 0..15 {
+                step(&mut app, 1);
+                top_speed = top_speed.max(velocity(&app, player).y.abs());
+            }
+            press(&mut app, if rep % 2 == 0 { KeyCode::W } else { KeyCode::S }, false);
+        }
+        let stamina = app.world.get::<Stamina>(player).unwrap();
+        assert!(stamina.speed_factor() < 1.);
+        assert!(top_speed > PLAYER_PADDLE_SPEED * 0.9);
+
+        // Slowed down while tired
+        press(&mut app, KeyCode::W, true);
+        step(&mut app, 15);
+        assert!(velocity(&app, player).y.abs() <= PLAYER_PADDLE_SPEED * STAMINA_SLOW_FACTOR + 1e-3);
+
+        press(&mut app, KeyCode::W, false);
+        step(&mut app, 400);
+        assert_eq!(app.world.get::<Stamina>(player).unwrap().current, STAMINA_MAX);
+    }
+
+    #[test]
+    fn magnet_curves_a_passing_ball_toward_it() {
+        let mut app = headless_app();
+        app.world
+            .spawn()
+            .insert(Magnet { strength: 600., radius: 150. })
+            .insert(Transform::from_xyz(0., 100., 0.));
+        let ball = spawn_test_ball(&mut app, Vec2::new(-50., 0.), Vec2::new(300., 0.));
+        step(&mut app, 1);
+
+        // Pulled toward the magnet by its strength, scaled down by how far out in its radius the ball is
+        let offset = Vec2::new(50., 100.);
+        let nudge = 600. * (1. - offset.length() / 150.) * TIME_STEP;
+        let expected = (Vec2::new(300., 0.) + offset.normalize() * nudge).normalize() * 300.;
+        assert!((velocity(&app, ball) - expected).length() < 1e-3);
+        assert!(velocity(&app, ball).y > 2.);
+    }
+
+    #[test]
+    fn solid_net_bounces_the_ball_back_outside_its_gap() {
+        let mut app = headless_app_with(|app| {
+            app.insert_resource(SolidNet { enabled: true, gap: 160. });
+        });
+        let blocked = spawn_test_ball(&mut app, Vec2::new(-30., 200.), Vec2::new(300., 0.));
+        let through_gap = spawn_test_ball(&mut app, Vec2::new(-30., 0.), Vec2::new(300., 0.));
+        step(&mut app, 20);
+
+        assert!(velocity(&app, blocked).x < 0.);
+        assert!(app.world.get::<Transform>(blocked).unwrap().translation.x < 0.);
+        assert!(velocity(&app, through_gap).x > 0.);
+        assert!(app.world.get::<Transform>(through_gap).unwrap().translation.x > 0.);
+    }
+
+    #[test]
+    fn first_serves_are_slower_until_onboarding_is_over() {
+        let mut app = headless_app();
+        app.insert_resource(Onboarding { serves: 3, served: 0 });
+        let speeds: Vec<f32> = (0..5)
+            .map(|_| {
+                let ball = serve_ball(&mut app);
+                let speed = velocity(&app, ball).length();
+                app.world.despawn(ball);
+                speed
+            })
+            .collect();
+
+        assert!(speeds[0] < speeds[1] && speeds[1] < speeds[2] && speeds[2] < BALL_SPEED);
+        assert!((speeds[0] - BALL_SPEED * ONBOARDING_SERVE_SPEED).abs() < 1e-3);
+        assert_eq!(speeds[3], BALL_SPEED);
+        assert_eq!(speeds[4], BALL_SPEED);
+    }
+
+    #[test]
+    fn reflect_bounce_mirrors_the_incoming_angle() {
+        let mut app = headless_app();
+        app.insert_resource(BounceMode::Reflect);
+        let incoming = Vec2::new(-(30f32.to_radians().cos()), 30f32.to_radians().sin()) * 400.;
+        let ball = spawn_test_ball(&mut app, Vec2::new(-300., -38.7), incoming);
+        step(&mut app, 20);
+
+        let outgoing = velocity(&app, ball);
+        assert!((outgoing.y.atan2(outgoing.x).to_degrees() - 30.).abs() < 1e-3);
+        assert!((outgoing.length() - 400.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn fast_ball_bounces_off_a_paddle_it_would_pass_right_through() {
+        let mut app = headless_app();
+        // Moves 50px a tick, far more than the paddle's width
+        let ball = spawn_test_ball(&mut app, Vec2::new(-340., 0.), Vec2::new(-3000., 0.));
+        step(&mut app, 2);
+
+        assert!(velocity(&app, ball).x > 0.);
+        let paddle_face = -DEFAULT_ARENA_WIDTH * 0.5 + 26. + PADDLE_SIZE.x * 0.5;
+        assert!(app.world.get::<Transform>(ball).unwrap().translation.x > paddle_face);
+    }
+
+    /// Score after leaving a still ball at the given X, above the paddles
+    fn score_with_ball_at(x: f32) -> (u16, u16) {
+        let mut app = headless_app();
+        simulate_ball(&mut app, Vec2::new(x, 200.), Vec2::ZERO, 1);
+        let scoreboard = app.world.resource::<Scoreboard>();
+        (scoreboard.player, scoreboard.opponent)
+    }
+
+    #[test]
+    fn gutters_score_at_mirrored_positions() {
+        // Ball's edge reaching the inner edge of either gutter
+        let threshold = DEFAULT_ARENA_WIDTH * 0.5 - GUTTER_INSET - GUTTER_WIDTH * 0.5 - BALL_SIZE.x * 0.5;
+        assert_eq!(score_with_ball_at(threshold + 0.5), (1, 0));
+        assert_eq!(score_with_ball_at(-threshold - 0.5), (0, 1));
+        assert_eq!(score_with_ball_at(threshold - 0.5), (0, 0));
+        assert_eq!(score_with_ball_at(-threshold + 0.5), (0, 0));
+    }
+
+    #[test]
+    fn ball_path_is_predicted_off_the_walls() {
+        let arena = Arena { width: DEFAULT_ARENA_WIDTH, height: DEFAULT_ARENA_HEIGHT };
+        let config = GameConfig::default();
+        let predict = |velocity| predict_ball_y(Vec2::ZERO, velocity, 400., &arena, &config);
+
+        assert_eq!(predict(Vec2::new(100., 0.)), Some(0.));
+        // Up to the top wall (296 up, allowing for the ball's size) and 104 back down
+        assert_eq!(predict(Vec2::new(100., 100.)), Some(192.));
+        // Off the top and bottom walls, then 312 back up
+        assert_eq!(predict(Vec2::new(100., 300.)), Some(16.));
+        assert_eq!(predict(Vec2::new(-100., 100.)), None);
+    }
+
+    #[test]
+    fn opponent_heads_for_where_the_ball_will_arrive() {
+        let mut app = headless_app();
+        // Off the top and bottom walls before it gets there
+        let ball_velocity = Vec2::new(200., 600.);
+        spawn_test_ball(&mut app, Vec2::ZERO, ball_velocity);
+        let mut query = app.world.query_filtered::<&Transform, With<Opponent>>();
+        let intercept_x = query.iter(&app.world).next().unwrap().translation.x - (PADDLE_SIZE.x + BALL_SIZE.x) * 0.5;
+        let arena = *app.world.resource::<Arena>();
+        let predicted_y = predict_ball_y(Vec2::ZERO, ball_velocity, intercept_x, &arena, &GameConfig::default()).unwrap();
+        assert!(predicted_y < -50.);
+
+        // Already heading down while the ball is still near the top
+        step(&mut app, 30);
+        assert!(query.iter(&app.world).next().unwrap().translation.y < -20.);
+
+        // Covering it just before the ball gets there (give or take how far past a wall the ball
+        // gets before bouncing off it)
+        step(&mut app, (intercept_x / ball_velocity.x / TIME_STEP) as u32 - 32);
+        let opponent_y = query.iter(&app.world).next().unwrap().translation.y;
+        assert!((opponent_y - predicted_y).abs() < PADDLE_SIZE.y * 0.5);
+    }
+
+    #[test]
+    fn paddle_takes_a_few_ticks_to_reach_top_speed_and_stop() {
+        let mut app = headless_app();
+        app.insert_resource(InputMode::Keyboard);
+        let mut query = app.world.query_filtered::<Entity, With<Player>>();
+        let player = query.iter(&app.world).next().unwrap();
+        let speeds = |app: &mut App, ticks| {
+            (0..ticks)
+                .map(|_| {
+                    step(app, 1);
+                    velocity(app, player).y
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Speeding up by the max acceleration each tick
+        let speed_up = PADDLE_MAX_ACCEL * TIME_STEP;
+        press(&mut app, KeyCode::W, true);
+        let ticks_to_top_speed = (PLAYER_PADDLE_SPEED / speed_up).ceil() as usize;
+        let ramp = speeds(&mut app, ticks_to_top_speed);
+        assert!((ramp[0] - speed_up).abs() < 1e-3);
+        assert!(ramp[ticks_to_top_speed - 2] < PLAYER_PADDLE_SPEED);
+        assert_eq!(ramp[ticks_to_top_speed - 1], PLAYER_PADDLE_SPEED);
+
+        // Slowing down faster, with friction too
+        press(&mut app, KeyCode::W, false);
+        let ticks_to_stop = (PLAYER_PADDLE_SPEED / ((PADDLE_MAX_ACCEL + PADDLE_FRICTION) * TIME_STEP)).ceil() as usize;
+        let stop = speeds(&mut app, ticks_to_stop);
+        assert!(stop[ticks_to_stop - 2] > 0.);
+        assert_eq!(stop[ticks_to_stop - 1], 0.);
+    }
+
+    #[test]
+    fn steep_bounce_is_scaled_back_to_the_speed_cap() {
+        let mut app = headless_app();
+        {
+            // Steep enough for the Y speed alone to be well over the cap
+            let mut config = app.world.resource_mut::<GameConfig>();
+            config.bounce_angle_multiplier = 200.;
+            config.max_bounce_y_speed = 5000.;
+        }
+        let ball = spawn_test_ball(&mut app, Vec2::new(-360., 20.), Vec2::new(-500., 0.));
+        step(&mut app, 1);
+
+        let bounced = velocity(&app, ball);
+        assert!((bounced.length() - MAX_BALL_SPEED).abs() < 1e-2);
+        // Still heading off at the angle it bounced at
+        let unclamped = Vec2::new(500. * RALLY_SPEED_RAMP, 20. * 200.);
+        assert!((bounced.normalize() - unclamped.normalize()).length() < 1e-4);
+    }
+
+    #[test]
+    fn ball_glances_off_the_top_and_bottom_edges_of_a_paddle() {
+        for side in [1., -1.] {
+            let mut app = headless_app();
+            // Dropping onto the paddle's edge, just in from its front corner
+            let ball = spawn_test_ball(&mut app, Vec2::new(-366., side * 41.), Vec2::new(-60., side * -300.));
+            step(&mut app, 10);
+
+            // Off the edge, carrying on the way it was going rather than back off the face
+            assert!(velocity(&app, ball).x < 0.);
+            assert!(velocity(&app, ball).y * side > 0.);
+            let ball_y = app.world.get::<Transform>(ball).unwrap().translation.y;
+            assert!(ball_y * side >= (PADDLE_SIZE.y + BALL_SIZE.y) * 0.5);
+        }
+    }
+
+    #[test]
+    fn ball_overlapping_a_paddle_bounces_once_and_ends_up_clear_of_it() {
+        // Already overlapping the face, and fast enough to end the tick halfway through the paddle
+        for (position, speed) in [(Vec2::new(-372., 0.), 300.), (Vec2::new(-364., 0.), 1000.)] {
+            let mut app = headless_app();
+            let ball = spawn_test_ball(&mut app, position, Vec2::new(-speed, 0.));
+            step(&mut app, 5);
+
+            assert_eq!(collision_events(&app), ["paddle"]);
+            assert!(velocity(&app, ball).x > 0.);
+            let paddle_face = -DEFAULT_ARENA_WIDTH * 0.5 + 26. + PADDLE_SIZE.x * 0.5;
+            assert!(app.world.get::<Transform>(ball).unwrap().translation.x - BALL_SIZE.x * 0.5 > paddle_face);
+        }
+    }
+
+    #[test]
+    fn input_latency_is_measured_only_while_shown() {
+        let mut app = headless_app();
+        app.insert_resource(InputMode::Keyboard);
+        press(&mut app, KeyCode::W, true);
+        step(&mut app, 1);
+        assert!(app.world.resource::<InputLatency>().measured.is_none());
+
+        app.world.resource_mut::<InputLatency>().measuring = true;
+        press(&mut app, KeyCode::W, false);
+        step(&mut app, 10);
+        press(&mut app, KeyCode::W, true);
+        // Waiting on the next tick to move the paddle
+        app.update();
+        assert!(app.world.resource::<InputLatency>().input_at.is_some());
+        step(&mut app, 1);
+        let input_latency = app.world.resource::<InputLatency>();
+        assert!(input_latency.input_at.is_none());
+        assert!(input_latency.measured.is_some());
+    }
+
+    #[test]
+    fn multi_ball_stops_splitting_at_the_cap_and_speeds_the_ball_up_instead() {
+        let mut app = headless_app();
+        app.insert_resource(MultiBall { enabled: true, max_balls: 2 });
+        let ball = spawn_test_ball(&mut app, Vec2::new(-360., 0.), Vec2::new(-500., 0.));
+        let mut balls = app.world.query_filtered::<Entity, With<Ball>>();
+
+        for round in 0..3 {
+            // Every hit is one that would bring another ball into play
+            app.insert_resource(RallyCount(MULTI_BALL_HITS_PER_BALL * (round + 1) - 1));
+            app.world.get_mut::<Transform>(ball).unwrap().translation = Vec3::new(-360., 0., 0.);
+            app.world.get_mut::<Velocity>(ball).unwrap().0 = Vec2::new(-500., 0.);
+            step(&mut app, 1);
+
+            assert_eq!(balls.iter(&app.world).count(), 2);
+            let expected = if round == 0 { 500. * RALLY_SPEED_RAMP } else { 500. * RALLY_SPEED_RAMP * MULTI_BALL_CAPPED_BOOST };
+            assert!((velocity(&app, ball).x - expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn rally_past_the_max_length_is_a_let_with_no_point_scored() {
+        let mut app = headless_app();
+        app.world.resource_mut::<GameConfig>().max_rally_length = Some(6);
+
+        // Reaching the limit carries on as usual
+        app.insert_resource(RallyCount(5));
+        let ball = spawn_test_ball(&mut app, Vec2::new(-360., 0.), Vec2::new(-500., 0.));
+        step(&mut app, 1);
+        assert!(app.world.get_entity(ball).is_some());
+
+        // Going past it calls the rally off
+        app.world.get_mut::<Transform>(ball).unwrap().translation = Vec3::new(-360., 0., 0.);
+        app.world.get_mut::<Velocity>(ball).unwrap().0 = Vec2::new(-500., 0.);
+        step(&mut app, 1);
+        assert!(app.world.get_entity(ball).is_none());
+        assert_eq!(app.world.resource::<Events<RallyLet>>().get_reader().iter(app.world.resource::<Events<RallyLet>>()).count(), 1);
+        let scoreboard = app.world.resource::<Scoreboard>();
+        assert_eq!((scoreboard.player, scoreboard.opponent), (0, 0));
+
+        // And the ball is served again from the middle
+        let served = serve_ball(&mut app);
+        assert!(app.world.get::<Transform>(served).unwrap().translation.x.abs() < BALL_SPEED * TIME_STEP * 1.5);
+        assert_eq!(app.world.resource::<RallyCount>().0, 0);
+    }
+
+    #[test]
+    fn win_by_two_plays_on_from_deuce_until_a_side_leads_by_two() {
+        let mut app = headless_app();
+        app.insert_resource(WinByTwo(true));
+        app.insert_resource(Scoreboard { player: 10, opponent: 10 });
+        app.update();
+
+        app.world.resource_mut::<Scoreboard>().player = 11;
+        app.update();
+        assert_eq!(app_state(&app), AppState::Playing);
+
+        app.world.resource_mut::<Scoreboard>().player = 12;
+        app.update();
+        assert_eq!(app_state(&app), AppState::GameOver);
+        assert_eq!(app.world.resource::<Series>().player_matches, 1);
+    }
+
+    #[test]
+    fn ball_shrinks_with_each_rally_hit_down_to_the_floor() {
+        let mut app = headless_app();
+        {
+            let mut config = app.world.resource_mut::<GameConfig>();
+            config.ball_shrink_per_hit = 0.1;
+            config.min_ball_scale = 0.6;
+        }
+        let ball_size = |app: &App, ball| app.world.get::<Sprite>(ball).unwrap().custom_size.unwrap();
+
+        // Third hit of the rally
+        app.insert_resource(RallyCount(2));
+        let ball = spawn_test_ball(&mut app, Vec2::new(-360., 0.), Vec2::new(-500., 0.));
+        step(&mut app, 1);
+        assert!((ball_size(&app, ball) - BALL_SIZE * 0.7).length() < 1e-4);
+
+        // Ninth hit, well past where it stops shrinking
+        app.insert_resource(RallyCount(8));
+        app.world.get_mut::<Transform>(ball).unwrap().translation = Vec3::new(-360., 0., 0.);
+        app.world.get_mut::<Velocity>(ball).unwrap().0 = Vec2::new(-500., 0.);
+        step(&mut app, 1);
+        assert!((ball_size(&app, ball) - BALL_SIZE * 0.6).length() < 1e-4);
+
+        // Served again at full size
+        app.world.despawn(ball);
+        let served = serve_ball(&mut app);
+        assert_eq!(ball_size(&app, served), BALL_SIZE);
+    }
+
+    #[test]
+    fn classic_mode_turns_off_every_modern_extra() {
+        let mut app = headless_app_with(|app| {
+            app.insert_resource(ClassicMode(true))
+                .insert_resource(BallTrail(true))
+                .insert_resource(BounceMode::Reflect)
+                .insert_resource(RandomServe { enabled: true, max_angle: 0.35 })
+                .insert_resource(StickyPaddle(true))
+                .insert_resource(MagnetMode(true))
+                .add_startup_system(apply_classic_mode);
+            app.world.resource_mut::<PowerUps>().enabled = true;
+            app.world.resource_mut::<MultiBall>().enabled = true;
+        });
+        app.update();
+
+        let config = app.world.resource::<GameConfig>();
+        assert_eq!(config.rally_speed_ramp, 1.);
+        assert_eq!(config.paddle_spin_factor, 0.);
+        assert!(*app.world.resource::<BounceMode>() == BounceMode::Positional);
+        assert!(!app.world.resource::<BallTrail>().0);
+        assert!(!app.world.resource::<RandomServe>().enabled);
+        assert!(!app.world.resource::<PowerUps>().enabled);
+        assert!(!app.world.resource::<MultiBall>().enabled);
+        assert!(!app.world.resource::<StickyPaddle>().0);
+        assert!(!app.world.resource::<MagnetMode>().0);
+    }
+
+    #[test]
+    fn paddle_hit_freezes_the_physics_for_the_hit_stop() {
+        for reduce_motion in [false, true] {
+            let mut app = headless_app();
+            app.insert_resource(ReduceMotion(reduce_motion))
+                .add_system(start_hit_stop.after(process_collisions));
+            // Lasts into a third tick
+            app.world.resource_mut::<HitStop>().duration = TIME_STEP * 2.5;
+            let ball = spawn_test_ball(&mut app, Vec2::new(-360., 0.), Vec2::new(-500., 0.));
+            let ball_x = |app: &App| app.world.get::<Transform>(ball).unwrap().translation.x;
+            step(&mut app, 1);
+            let hit_at = ball_x(&app);
+
+            step(&mut app, 3);
+            let travel = 500. * RALLY_SPEED_RAMP * TIME_STEP;
+            let expected_ticks = if reduce_motion { 3. } else { 0. };
+            assert!((ball_x(&app) - (hit_at + travel * expected_ticks)).abs() < 1e-3);
+
+            // Then carries on as before
+            step(&mut app, 1);
+            assert!((ball_x(&app) - (hit_at + travel * (expected_ticks + 1.))).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn corner_serves_alternate_top_and_bottom_heading_into_the_arena() {
+        let mut app = headless_app();
+        app.insert_resource(ServeSpawn::Corners { top: true });
+
+        for side_y in [1., -1., 1.] {
+            let ball = serve_ball(&mut app);
+            let position = app.world.get::<Transform>(ball).unwrap().translation;
+            let velocity = velocity(&app, ball);
+            assert_eq!(position.y.signum(), side_y);
+            assert!(position.y.abs() > DEFAULT_ARENA_HEIGHT * 0.25);
+            // Away from the wall it started by, and from the server's side
+            assert_eq!(velocity.y.signum(), -side_y);
+            assert_eq!(velocity.x.signum(), -position.x.signum());
+            app.world.despawn(ball);
+        }
+    }
+
+    #[test]
+    fn losing_the_gamepad_pauses_until_it_reconnects_or_input_mode_switches() {
+        let mut app = headless_app();
+        app.add_event::<GamepadEvent>()
+            .add_system(track_gamepads)
+            .add_system(toggle_input_mode)
+            .add_system(toggle_pause);
+        let gamepad_event = |app: &mut App, event_type| {
+            app.world.resource_mut::<Events<GamepadEvent>>().send(GamepadEvent(Gamepad(0), event_type));
+            app.update();
+        };
+        gamepad_event(&mut app, GamepadEventType::Connected);
+        assert_eq!(app_state(&app), AppState::Playing);
+
+        gamepad_event(&mut app, GamepadEventType::Disconnected);
+        assert_eq!(app_state(&app), AppState::Paused);
+        // The usual unpause doesn't resume, no one's at the controls
+        press(&mut app, KeyCode::Escape, true);
+        app.update();
+        assert_eq!(app_state(&app), AppState::Paused);
+        press(&mut app, KeyCode::Escape, false);
+
+        gamepad_event(&mut app, GamepadEventType::Connected);
+        assert_eq!(app_state(&app), AppState::Playing);
+        assert_eq!(app.world.resource::<ActiveGamepad>().0, Some(Gamepad(0)));
+
+        // Or carry on with the mouse or keyboard instead
+        gamepad_event(&mut app, GamepadEventType::Disconnected);
+        assert_eq!(app_state(&app), AppState::Paused);
+        press(&mut app, KeyCode::Tab, true);
+        app.update();
+        assert_eq!(app_state(&app), AppState::Playing);
+        assert!(*app.world.resource::<InputMode>() == InputMode::Keyboard);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn period_steps_a_single_physics_tick_while_paused() {
+        let mut app = headless_app();
+        app.add_system_set(SystemSet::on_update(AppState::Paused).with_system(step_once));
+        let ball = spawn_test_ball(&mut app, Vec2::ZERO, Vec2::new(300., 0.));
+        let ball_x = |app: &App| app.world.get::<Transform>(ball).unwrap().translation.x;
+        app.world.resource_mut::<State<AppState>>().push(AppState::Paused).unwrap();
+        app.update();
+        app.update();
+        assert_eq!(ball_x(&app), 0.);
+
+        press(&mut app, KeyCode::Period, true);
+        app.update();
+        press(&mut app, KeyCode::Period, false);
+        app.update();
+        app.update();
+        assert!((ball_x(&app) - 300. * TIME_STEP).abs() < 1e-4);
+        assert_eq!(app_state(&app), AppState::Paused);
+    }
+
+    #[test]
+    fn multi_balls_each_get_their_own_color_which_their_trails_share() {
+        let mut app = headless_app();
+        app.insert_resource(MultiBall { enabled: true, max_balls: 3 })
+            .insert_resource(BallTrail(true))
+            .insert_resource(ReduceMotion(false))
+            .add_system(spawn_ball_trail);
+        let ball = serve_ball(&mut app);
+        for rally_count in [3, 7] {
+            // Every hit is one that brings another ball into play
+            app.insert_resource(RallyCount(rally_count));
+            app.world.get_mut::<Transform>(ball).unwrap().translation = Vec3::new(-360., 0., 0.);
+            app.world.get_mut::<Velocity>(ball).unwrap().0 = Vec2::new(-500., 0.);
+            step(&mut app, 1);
+        }
+        // Trailing each ball where it's come to rest, clear of the ghosts left along the way
+        let mut ghosts = app.world.query_filtered::<Entity, With<TrailFade>>();
+        for ghost in ghosts.iter(&app.world).collect::<Vec<_>>() {
+            app.world.despawn(ghost);
+        }
+        app.update();
+
+        let mut balls = app.world.query_filtered::<(&Transform, &Sprite, &BallColor), With<Ball>>();
+        let balls: Vec<(Vec3, Color, Color)> = balls
+            .iter(&app.world)
+            .map(|(transform, sprite, ball_color)| (transform.translation, sprite.color, ball_color.0))
+            .collect();
+        assert_eq!(balls.len(), 3);
+        for (index, (_, sprite_color, ball_color)) in balls.iter().enumerate() {
+            assert_eq!(sprite_color, ball_color);
+            assert!(balls[index + 1..].iter().all(|(_, _, other)| other != ball_color));
+        }
+
+        let mut trails = app.world.query_filtered::<(&Transform, &Sprite), With<TrailFade>>();
+        for (position, color, _) in &balls {
+            let (_, trail) = trails
+                .iter(&app.world)
+                .find(|(transform, _)| transform.translation.truncate() == position.truncate())
+                .expect("ball has no trail");
+            assert_eq!(trail.color.as_rgba_f32()[..3], color.as_rgba_f32()[..3]);
+        }
+    }
+
+    fn paddle<T: Component>(app: &mut App) -> Entity {
+        let mut query = app.world.query_filtered::<Entity, With<T>>();
+        query.iter(&app.world).next().unwrap()
+    }
+
+    #[test]
+    fn swapping_a_paddles_input_source_changes_what_drives_it() {
+        let mut app = headless_app();
+        // High up and heading for the right paddle, so the AI would chase it up while Down is held
+        spawn_test_ball(&mut app, Vec2::new(0., 200.), Vec2::new(300., 0.));
+        let opponent = paddle::<Opponent>(&mut app);
+        press(&mut app, KeyCode::Down, true);
+        // Sources are assigned from the settings on entering the match
+        app.update();
+
+        app.world.resource_mut::<InputSources>().opponent = InputSource::Keyboard;
+        step(&mut app, 5);
+        assert!(velocity(&app, opponent).y < 0.);
+        assert_eq!(app.world.resource::<OpponentTarget>().0, None);
+
+        app.world.resource_mut::<InputSources>().opponent = InputSource::Ai;
+        step(&mut app, 20);
+        assert!(velocity(&app, opponent).y > 0.);
+        assert!(app.world.resource::<OpponentTarget>().0.is_some());
+
+        // And back again
+        app.world.resource_mut::<InputSources>().opponent = InputSource::Keyboard;
+        step(&mut app, 20);
+        assert!(velocity(&app, opponent).y < 0.);
+    }
+
+    #[test]
+    fn input_sources_follow_the_two_player_setting() {
+        let mut app = headless_app();
+        let sources = *app.world.resource::<InputSources>();
+        assert_eq!((sources.player, sources.opponent), (InputSource::Mouse, InputSource::Ai));
+
+        app.insert_resource(TwoPlayer(true));
+        app.update();
+        let sources = *app.world.resource::<InputSources>();
+        assert_eq!((sources.player, sources.opponent), (InputSource::Keyboard, InputSource::Keyboard));
+    }
+
+    #[test]
+    fn match_point_is_on_exactly_when_a_side_is_one_goal_from_winning() {
+        let mut app = headless_app();
+        app.insert_resource(WinningScore(11));
+        let on_match_point = |app: &mut App, player, opponent| {
+            app.insert_resource(Scoreboard { player, opponent });
+            app.update();
+            app.world.resource::<MatchPoint>().0
+        };
+
+        assert!(!on_match_point(&mut app, 9, 9));
+        assert!(on_match_point(&mut app, 10, 9));
+        assert!(on_match_point(&mut app, 3, 10));
+        assert!(on_match_point(&mut app, 10, 10));
+
+        app.insert_resource(WinByTwo(true));
+        assert!(!on_match_point(&mut app, 10, 10));
+        assert!(on_match_point(&mut app, 11, 10));
+        // Lapses once the trailing side pulls level
+        assert!(!on_match_point(&mut app, 11, 11));
+    }
+
+    #[test]
+    fn match_point_border_and_banner_show_only_while_on_match_point() {
+        let mut app = headless_app_with(|app| {
+            app.insert_resource(MatchPointEmphasis(true))
+                .insert_resource(ReduceMotion(true))
+                .insert_resource(WinningScore(11))
+                .add_system(show_match_point.after(detect_match_point));
+        });
+        let border = app.world.spawn().insert_bundle(SpriteBundle::default()).insert(MatchPointBorder(Vec2::Y)).id();
+        let banner = app.world.spawn().insert_bundle(Text2dBundle::default()).insert(MatchPointBanner).id();
+        let shown = |app: &App| {
+            let visible = |entity| app.world.get::<Visibility>(entity).unwrap().is_visible;
+            (visible(border), visible(banner))
+        };
+
+        app.insert_resource(Scoreboard { player: 9, opponent: 9 });
+        app.update();
+        assert_eq!(shown(&app), (false, false));
+
+        app.insert_resource(Scoreboard { player: 10, opponent: 9 });
+        app.update();
+        assert_eq!(shown(&app), (true, true));
+        // Along the top edge, holding steady with motion reduced
+        let arena = *app.world.resource::<Arena>();
+        let top = app.world.get::<Transform>(border).unwrap().translation.y;
+        assert!((top - (arena.height - MATCH_POINT_BORDER_THICKNESS) * 0.5).abs() < 1e-3);
+        assert_eq!(app.world.get::<Sprite>(border).unwrap().color.a(), 1.);
+
+        app.insert_resource(MatchPointEmphasis(false));
+        app.update();
+        assert_eq!(shown(&app), (false, false));
+
+        app.insert_resource(MatchPointEmphasis(true));
+        app.insert_resource(Scoreboard { player: 10, opponent: 10 });
+        app.insert_resource(WinByTwo(true));
+        app.update();
+        assert_eq!(shown(&app), (false, false));
+    }
+
+    #[test]
+    fn endless_ai_gets_faster_the_longer_the_player_survives() {
+        let opponent_speed = |survived| {
+            let mut app = headless_app();
+            app.insert_resource(Endless { enabled: true, lives: ENDLESS_LIVES, survived })
+                .insert_resource(Difficulty::Medium);
+            app.world.resource_mut::<GameConfig>().paddle_max_accel = f32::INFINITY;
+            // Far enough above the paddle that the AI heads for it flat out
+            spawn_test_ball(&mut app, Vec2::new(0., 250.), Vec2::new(300., 0.));
+            let opponent = paddle::<Opponent>(&mut app);
+            let mut top_speed: f32 = 0.;
+            for _ in 0..20 {
+                step(&mut app, 1);
+                top_speed = top_speed.max(velocity(&app, opponent).y);
+            }
+            (top_speed, app.world.resource::<Endless>().survived)
+        };
+
+        let (starting_speed, survived) = opponent_speed(0.);
+        assert!((survived - 20. * TIME_STEP).abs() < 1e-4);
+        assert!(starting_speed > 0.);
+        let (ramped_speed, _) = opponent_speed(ENDLESS_RAMP_TIME * 0.5);
+        assert!(ramped_speed > starting_speed * 1.4);
+        let (hardest_speed, _) = opponent_speed(ENDLESS_RAMP_TIME * 2.);
+        assert!((hardest_speed / starting_speed - ENDLESS_MAX_AI_BOOST).abs() < 0.01);
+    }
+
+    #[test]
+    fn endless_ends_once_the_player_concedes_their_lives() {
+        let mut app = headless_app();
+        app.insert_resource(Endless { enabled: true, lives: 3, survived: 0. });
+
+        // No winning score to reach
+        app.insert_resource(Scoreboard { player: 20, opponent: 2 });
+        app.update();
+        assert_eq!(app_state(&app), AppState::Playing);
+        assert!(app.world.resource::<MatchPoint>().0);
+
+        app.insert_resource(Scoreboard { player: 20, opponent: 3 });
+        app.update();
+        assert_eq!(app_state(&app), AppState::GameOver);
+        assert_eq!(app.world.resource::<Series>().opponent_matches, 1);
+    }
+
+    #[test]
+    fn rematching_accumulates_session_stats_while_match_stats_reset() {
+        let mut app = headless_app_with(|app| {
+            app.insert_resource(SessionStats::default())
+                .insert_resource(CarrySessionStats(true))
+                .insert_resource(WinningScore(11))
+                .add_event::<MenuAction>()
+                .add_system(record_session_stats.after(check_game_over))
+                .add_system_set(SystemSet::on_update(AppState::GameOver).with_system(apply_game_over_actions));
+        });
+        let finish_match = |app: &mut App, rallies, longest_rally| {
+            app.insert_resource(MatchStats { rallies, longest_rally, ..default() });
+            app.insert_resource(Scoreboard { player: 11, opponent: 4 });
+            app.update();
+            assert_eq!(app_state(app), AppState::GameOver);
+        };
+        let choose = |app: &mut App, action| {
+            app.world.resource_mut::<Events<MenuAction>>().send(action);
+            app.update();
+        };
+        let session = |app: &App| {
+            let session_stats = app.world.resource::<SessionStats>();
+            (session_stats.matches, session_stats.rallies, session_stats.longest_rally)
+        };
+
+        finish_match(&mut app, 5, 4);
+        choose(&mut app, MenuAction::Rematch);
+        assert_eq!(app_state(&app), AppState::Playing);
+        assert_eq!(app.world.resource::<MatchStats>().rallies, 0);
+        assert_eq!(session(&app), (1, 5, 4));
+
+        finish_match(&mut app, 3, 7);
+        assert_eq!(session(&app), (2, 8, 7));
+
+        // A fresh session from the title screen
+        choose(&mut app, MenuAction::MainMenu);
+        assert_eq!(app_state(&app), AppState::Menu);
+        assert_eq!(session(&app), (0, 0, 0));
+    }
+
+    #[test]
+    fn rematch_starts_a_new_session_unless_carrying_stats_over() {
+        let mut app = headless_app_with(|app| {
+            app.insert_resource(SessionStats::default())
+                .insert_resource(CarrySessionStats(false))
+                .insert_resource(WinningScore(11))
+                .add_event::<MenuAction>()
+                .add_system(record_session_stats.after(check_game_over))
+                .add_system_set(SystemSet::on_update(AppState::GameOver).with_system(apply_game_over_actions));
+        });
+        app.insert_resource(MatchStats { rallies: 5, longest_rally: 4, ..default() });
+        app.insert_resource(Scoreboard { player: 11, opponent: 4 });
+        app.update();
+        assert_eq!(app.world.resource::<SessionStats>().matches, 1);
+
+        app.world.resource_mut::<Events<MenuAction>>().send(MenuAction::Rematch);
+        app.update();
+        assert_eq!(app_state(&app), AppState::Playing);
+        assert_eq!(app.world.resource::<SessionStats>().matches, 0);
+    }
+
+    #[test]
+    fn camera_is_recentered_by_the_time_the_ball_is_served() {
+        let mut app = headless_app_with(|app| {
+            app.insert_resource(CameraRecenter(true))
+                .insert_resource(ReduceMotion(false))
+                .add_system(recenter_camera.after(ball_spawner).before(zoom_camera))
+                .add_system(zoom_camera);
+        });
+        // Knocked off center, and part way through a punch
+        let camera = app.world
+            .spawn()
+            .insert_bundle(OrthographicCameraBundle::new_2d())
+            .insert(MainCamera)
+            .insert(CameraZoom { fit: 1.5, punch: Timer::from_seconds(CAMERA_PUNCH_DURATION, false) })
+            .id();
+        app.world.get_mut::<Transform>(camera).unwrap().translation = Vec3::new(12., -8., 999.9);
+        app.world.resource_mut::<BallSpawnTimer>().0.reset();
+
+        // Part way there half way through the countdown, a tick each frame
+        let countdown_ticks = (SERVE_COUNTDOWN / TIME_STEP).round() as u32;
+        for _ in 0..countdown_ticks / 2 {
+            step(&mut app, 1);
+        }
+        let translation = app.world.get::<Transform>(camera).unwrap().translation;
+        assert!(translation.x > 1. && translation.x < 12.);
+
+        for _ in countdown_ticks / 2..countdown_ticks {
+            step(&mut app, 1);
+        }
+        assert!(ball_position(&mut app).is_some());
+        assert_eq!(app.world.get::<Transform>(camera).unwrap().translation, Vec3::new(0., 0., 999.9));
+        assert!(app.world.get::<CameraZoom>(camera).unwrap().punch.finished());
+        assert_eq!(app.world.get::<OrthographicProjection>(camera).unwrap().scale, 1.5);
+    }
+
+    #[test]
+    fn comeback_cue_fires_once_per_goal_scored_from_the_gap_or_more_behind() {
+        let mut app = headless_app();
+        app.insert_resource(ComebackGap(3));
+        app.insert_resource(Scoreboard { player: 0, opponent: 5 });
+        let mut comebacks = app.world.resource::<Events<Comeback>>().get_reader();
+        let mut player_scores = |app: &mut App| {
+            simulate_ball(app, Vec2::new(360., 200.), Vec2::new(300., 0.), 10);
+            comebacks.iter(app.world.resource::<Events<Comeback>>()).count()
+        };
+
+        // From 5, 4 and 3 behind it's a comeback, closer than that it's just a goal
+        for expected in [1, 1, 1, 0, 0] {
+            assert_eq!(player_scores(&mut app), expected);
+        }
+        let scoreboard = app.world.resource::<Scoreboard>();
+        assert_eq!((scoreboard.player, scoreboard.opponent), (5, 5));
+
+        // Off when the gap is 0
+        app.insert_resource(ComebackGap(0));
+        app.insert_resource(Scoreboard { player: 0, opponent: 5 });
+        assert_eq!(player_scores(&mut app), 0);
+    }
+
+    #[test]
+    fn solid_net_stops_a_ball_at_max_speed_from_tunnelling_through() {
+        let mut app = headless_app_with(|app| {
+            app.insert_resource(SolidNet { enabled: true, gap: 160. });
+        });
+        // Covering more than the net and ball's combined width each tick, from both sides and at
+        // every offset into a tick
+        let travel = MAX_BALL_SPEED * TIME_STEP;
+        for direction in [1., -1.] {
+            for start in 0..4 {
+                let start_x = -direction * (20. + travel * start as f32 / 4.);
+                let ball = spawn_test_ball(&mut app, Vec2::new(start_x, 200.), Vec2::new(direction * MAX_BALL_SPEED, 0.));
+                step(&mut app, 3);
+
+                assert_eq!(velocity(&app, ball).x.signum(), -direction);
+                assert_eq!(app.world.get::<Transform>(ball).unwrap().translation.x.signum(), -direction);
+                app.world.despawn(ball);
+            }
+        }
+    }
+
+    #[test]
+    fn reflect_bounce_keeps_the_ball_speed_with_a_full_table_and_a_moving_paddle() {
+        let mut app = headless_app();
+        app.insert_resource(BounceMode::Reflect);
+        app.world.resource_mut::<MultiBall>().enabled = true;
+        app.world.resource_mut::<MultiBall>().max_balls = 1;
+        app.insert_resource(RallyCount(MULTI_BALL_HITS_PER_BALL - 1));
+        // Off the opponent paddle while it's moving, so its spin changes the angle of the bounce
+        let incoming = Vec2::new(30f32.to_radians().cos(), -(30f32.to_radians().sin())) * 400.;
+        let ball = spawn_test_ball(&mut app, Vec2::new(300., 80.), incoming);
+        step(&mut app, 20);
+
+        let outgoing = velocity(&app, ball);
+        assert!(outgoing.x < 0.);
+        assert!((outgoing.y.atan2(-outgoing.x).to_degrees() + 30.).abs() > 1.);
+        assert!((outgoing.length() - 400.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn instant_replay_swaps_the_paddles_and_balls_for_ghosts_until_stopped() {
+        let mut app = headless_app_with(|app| {
+            app.insert_resource(ReplayPlayback(None))
+                .add_system_set(
+                    SystemSet::on_update(AppState::Paused)
+                        .with_system(toggle_instant_replay.before(play_instant_replay))
+                )
+                .add_system(play_instant_replay);
+        });
+        let ball = spawn_test_ball(&mut app, Vec2::ZERO, Vec2::new(100., 0.));
+        step(&mut app, 10);
+        app.world.resource_mut::<State<AppState>>().push(AppState::Paused).unwrap();
+        app.update();
+        let mut ghosts = app.world.query_filtered::<&Transform, With<ReplayGhost>>();
+        let visible = |app: &App| app.world.get::<Visibility>(ball).unwrap().is_visible;
+
+        // Two paddles and the ball, as they were on the oldest tick held
+        press(&mut app, KeyCode::R, true);
+        app.update();
+        let oldest_ball = app.world.resource::<ReplayBuffer>().frames[0].balls[0];
+        assert_eq!(ghosts.iter(&app.world).count(), 3);
+        assert!(ghosts.iter(&app.world).any(|transform| transform.translation.truncate() == oldest_ball));
+        assert!(!visible(&app));
+
+        press(&mut app, KeyCode::R, false);
+        app.update();
+        press(&mut app, KeyCode::R, true);
+        app.update();
+        assert_eq!(ghosts.iter(&app.world).count(), 0);
+        assert!(visible(&app));
+    }
+
+    #[test]
+    fn sources_assigned_in_the_settings_outlast_other_input_setting_changes() {
+        let mut app = headless_app();
+        let opponent = paddle::<Opponent>(&mut app);
+        let gamepad = Gamepad(1);
+        app.world.resource_mut::<InputAssignments>().opponent = Some(InputSource::Gamepad(gamepad));
+        app.update();
+
+        app.insert_resource(InputMode::Keyboard);
+        app.insert_resource(TwoPlayer(true));
+        app.update();
+        let sources = *app.world.resource::<InputSources>();
+        assert_eq!((sources.player, sources.opponent), (InputSource::Keyboard, InputSource::Gamepad(gamepad)));
+
+        // Driven by that gamepad's stick
+        app.world.resource_mut::<Axis<GamepadAxis>>().set(GamepadAxis(gamepad, GamepadAxisType::LeftStickY), 1.);
+        step(&mut app, 5);
+        assert!(velocity(&app, opponent).y > 0.);
+
+        // Back to the automatic source once unassigned
+        app.world.resource_mut::<InputAssignments>().opponent = None;
+        app.update();
+        assert_eq!(app.world.resource::<InputSources>().opponent, InputSource::Keyboard);
+    }
+
+    #[test]
+    fn target_practice_sets_out_targets_again_once_all_are_knocked_out() {
+        let mut app = headless_app();
+        let mut targets = app.world.query_filtered::<(Entity, &Transform), With<Target>>();
+        step(&mut app, 2);
+        assert_eq!(targets.iter(&app.world).count(), 0);
+
+        app.insert_resource(TargetPractice(true));
+        step(&mut app, 1);
+        let placed: Vec<(Entity, Vec3)> =
+            targets.iter(&app.world).map(|(target, transform)| (target, transform.translation)).collect();
+        assert_eq!(placed.len(), TARGET_COUNT);
+        assert!(placed.iter().all(|(_, position)| position.x > 0. && position.y.abs() < DEFAULT_ARENA_HEIGHT * 0.5));
+
+        // Knocking out all but one leaves it be
+        let ball = spawn_test_ball(&mut app, placed[0].1.truncate() - Vec2::new(20., 0.), Vec2::new(300., 0.));
+        step(&mut app, 10);
+        app.world.despawn(ball);
+        app.world.despawn(placed[1].0);
+        step(&mut app, 2);
+        assert_eq!(targets.iter(&app.world).count(), 1);
+
+        app.world.despawn(placed[2].0);
+        step(&mut app, 2);
+        assert_eq!(targets.iter(&app.world).count(), TARGET_COUNT);
+    }
+
+#[test]
+fn input_latency_readout_shows_the_average_latest_and_range() {
+    assert_eq!(input_latency_readout(None), "Input latency: - (move the paddle)");
+
+    let mut diagnostic = Diagnostic::new(InputLatency::DIAGNOSTIC, "input_latency", 20);
+    for latency in [20., 10., 30.] {
+        diagnostic.add_measurement(latency);
+    }
+    assert_eq!(
+        input_latency_readout(Some(&diagnostic)),
+        "Input latency: 20.0 ms\nLatest: 30.0 ms\nRange: 10.0-30.0 ms over 3 inputs"
+    );
+}
+}
@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+use crate::components::*;
+use crate::constants::*;
+use crate::plugin::PongSimulationPlugin;
+use crate::resources::*;
+use crate::systems::finished_timer;
+
+
+/// Headless app running just the simulation, already in a match (paddles spawned, no serve
+/// pending), with the physics only stepped by `step`
+pub fn headless_app() -> App {
+    headless_app_with(|_| {})
+}
+
+
+/// Headless app like `headless_app`, configured before the match starts (e.g. to set up modes
+/// that take effect when entering it)
+pub fn headless_app_with(configure: impl FnOnce(&mut App)) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(PongSimulationPlugin {
+            arena: Arena { width: DEFAULT_ARENA_WIDTH, height: DEFAULT_ARENA_HEIGHT },
+            seed: DEFAULT_SEED,
+        })
+        .insert_resource(PhysicsClock { manual: true, ..default() })
+        .insert_resource(BallSpawnTimer(finished_timer(SERVE_COUNTDOWN)));
+    configure(&mut app);
+    app.world.resource_mut::<State<AppState>>().set(AppState::Playing).unwrap();
+    app.update();
+    app
+}
+
+
+/// Run exactly `ticks` physics ticks
+pub fn step(app: &mut App, ticks: u32) {
+    app.world.resource_mut::<PhysicsClock>().queued_steps = ticks;
+    app.update();
+}
+
+
+/// Serve the next ball straight away, returning it
+pub fn serve_ball(app: &mut App) -> Entity {
+    let mut query = app.world.query_filtered::<Entity, With<Ball>>();
+    let balls: Vec<Entity> = query.iter(&app.world).collect();
+    app.insert_resource(BallSpawnTimer(Timer::from_seconds(TIME_STEP, false)));
+    step(app, 1);
+    query.iter(&app.world).find(|ball| !balls.contains(ball)).expect("no ball was served")
+}
+
+
+/// Put a ball in play at the given position and velocity, as if the opponent had served it a while ago
+pub fn spawn_test_ball(app: &mut App, position: Vec2, velocity: Vec2) -> Entity {
+    let config = *app.world.resource::<GameConfig>();
+    app.world
+        .spawn()
+        .insert(Ball)
+        .insert(Gameplay)
+        .insert(Serve { immunity: finished_timer(SERVE_GUTTER_IMMUNITY), by_player: false })
+        .insert(Velocity(velocity))
+        .insert(StuckDetector { anchor: position, ..default() })
+        .insert_bundle(SpriteBundle {
+            transform: Transform::from_translation(position.extend(0.)),
+            sprite: Sprite {
+                custom_size: Some(config.ball_size),
+                ..default()
+            },
+            ..default()
+        })
+        .id()
+}
+
+
+/// Spawn a ball and step the physics, returning where the ball ended up (None if it's gone, e.g.
+/// into a goal)
+pub fn simulate_ball(app: &mut App, position: Vec2, velocity: Vec2, ticks: u32) -> Option<Transform> {
+    let ball = spawn_test_ball(app, position, velocity);
+    step(app, ticks);
+    app.world.get::<Transform>(ball).copied()
+}
+
+
+/// Current velocity of an entity
+pub fn velocity(app: &App, entity: Entity) -> Vec2 {
+    app.world.get::<Velocity>(entity).unwrap().0
+}